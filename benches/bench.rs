@@ -4,7 +4,7 @@ extern crate fdlimit;
 extern crate test;
 extern crate tiny_http;
 
-use std::io::Write;
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::process::Command;
 use tiny_http::Method;
 
@@ -78,3 +78,81 @@ fn parallel_requests(bencher: &mut test::Bencher) {
         }
     });
 }
+
+/// Size of the file downloaded by the file benchmarks.
+const FILE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Serves a large file with the responses built by `response`, and measures how long it takes
+/// to download it.
+fn download_file<F>(bencher: &mut test::Bencher, name: &str, response: F)
+where
+    F: Fn(std::fs::File) -> tiny_http::ResponseBox + Send + 'static,
+{
+    let path =
+        std::env::temp_dir().join(format!("tiny-http-bench-{}-{}", std::process::id(), name));
+    std::fs::write(&path, vec![b'x'; FILE_SIZE]).unwrap();
+
+    let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    let port = server.server_addr().to_ip().unwrap().port();
+
+    let served = path.clone();
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let file = std::fs::File::open(&served).unwrap();
+            request.respond(response(file)).unwrap();
+        }
+    });
+
+    let stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut stream = stream;
+
+    bencher.bytes = FILE_SIZE as u64;
+    bencher.iter(|| {
+        (write!(stream, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+
+        let mut line = String::new();
+        let mut content_length = None;
+        loop {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                content_length = Some(value.trim().parse::<u64>().unwrap());
+            }
+        }
+
+        let length = content_length.unwrap();
+        let copied = io::copy(&mut reader.by_ref().take(length), &mut io::sink()).unwrap();
+        assert_eq!(copied, FILE_SIZE as u64);
+    });
+
+    std::fs::remove_file(&path).ok();
+}
+
+#[bench]
+fn file_download(bencher: &mut test::Bencher) {
+    // sent with `sendfile` on Linux
+    download_file(bencher, "direct", |file| {
+        tiny_http::Response::from_file(file).boxed()
+    });
+}
+
+#[bench]
+fn file_download_copied(bencher: &mut test::Bencher) {
+    // the same file, hidden behind another reader so that it is copied through userspace
+    download_file(bencher, "copied", |file| {
+        let length = file.metadata().unwrap().len() as usize;
+        tiny_http::Response::new(
+            tiny_http::StatusCode(200),
+            Vec::new(),
+            BufReader::new(file),
+            Some(length),
+            None,
+        )
+        .with_chunked_threshold(usize::MAX)
+        .boxed()
+    });
+}
@@ -2,15 +2,20 @@ use ascii::AsciiString;
 
 use std::io::Error as IoError;
 use std::io::Result as IoResult;
-use std::io::{BufReader, BufWriter, ErrorKind, Read};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
 
 use std::net::SocketAddr;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::common::{HTTPVersion, Method};
+use crate::drain::Drain;
+use crate::util::refined_tcp_stream::ReadTimeout;
 use crate::util::RefinedTcpStream;
 use crate::util::{SequentialReader, SequentialReaderBuilder, SequentialWriterBuilder};
-use crate::Request;
+use crate::{Limits, ListenAddr, Request, Timeouts};
 
 /// A ClientConnection is an object that will store a socket to a client
 /// and return Request objects.
@@ -18,6 +23,9 @@ pub struct ClientConnection {
     // address of the client
     remote_addr: IoResult<Option<SocketAddr>>,
 
+    // address of the listener that accepted the connection
+    listen_addr: ListenAddr,
+
     // sequence of Readers to the stream, so that the data is not read in
     //  the wrong order
     source: SequentialReaderBuilder<BufReader<RefinedTcpStream>>,
@@ -32,17 +40,48 @@ pub struct ClientConnection {
     // set to true if we know that the previous request is the last one
     no_more_requests: bool,
 
+    // set to true once the body of the previous request turns out to be larger than its
+    // limit, in which case the end of this body is never read
+    body_too_large: Option<Arc<AtomicBool>>,
+
+    // true until the first request has been read
+    first_request: bool,
+
     // true if the connection goes through SSL
     secure: bool,
+
+    // timeouts to apply while reading from the client
+    timeouts: Timeouts,
+
+    // limits on the size of the requests
+    limits: Limits,
+
+    // controls the read timeouts of the socket
+    read_timeout: ReadTimeout,
+
+    // tells whether the server is shutting down
+    drain: Arc<Drain>,
 }
 
+/// Maximum time spent discarding the data sent by a client after an error response.
+const DISCARD_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Maximum amount of data discarded after an error response.
+pub(crate) const DISCARD_MAX_LEN: u64 = 64 * 1024;
+
 /// Error that can happen when reading a request.
 #[derive(Debug)]
 enum ReadError {
     WrongRequestLine,
+    /// the request line is longer than allowed by the limits
+    RequestLineTooLong,
     WrongHeader(HTTPVersion),
+    /// the headers exceed one of the limits
+    HeadersTooLarge(HTTPVersion),
     /// the client sent an unrecognized `Expect` header
     ExpectationFailed(HTTPVersion),
+    /// the client announced a body larger than the limit
+    PayloadTooLarge(HTTPVersion),
     ReadIoError(IoError),
 }
 
@@ -51,9 +90,14 @@ impl ClientConnection {
     pub fn new(
         write_socket: RefinedTcpStream,
         mut read_socket: RefinedTcpStream,
+        listen_addr: ListenAddr,
+        timeouts: Timeouts,
+        limits: Limits,
+        drain: Arc<Drain>,
     ) -> ClientConnection {
         let remote_addr = read_socket.peer_addr();
         let secure = read_socket.secure();
+        let read_timeout = read_socket.read_timeout();
 
         let mut source = SequentialReaderBuilder::new(BufReader::with_capacity(1024, read_socket));
         let first_header = source.next().unwrap();
@@ -62,22 +106,50 @@ impl ClientConnection {
             source,
             sink: SequentialWriterBuilder::new(BufWriter::with_capacity(1024, write_socket)),
             remote_addr,
+            listen_addr,
             next_header_source: first_header,
             no_more_requests: false,
+            body_too_large: None,
+            first_request: true,
             secure,
+            timeouts,
+            limits,
+            read_timeout,
+            drain,
         }
     }
 
-    /// true if the connection is HTTPS
-    pub fn secure(&self) -> bool {
-        self.secure
+    /// Reads and drops what the client may still be sending after an error response, before
+    /// the connection is closed.
+    ///
+    /// Closing a socket while some of the data received is unread makes the OS reset the
+    /// connection, which can destroy the response before the client reads it.
+    pub(crate) fn discard_input(&mut self) {
+        // without SSL, the client knows that no more data is coming once it has read the
+        // response
+        if let Some(mut writer) = self.sink.next() {
+            writer.flush().ok();
+        }
+        self.next_header_source
+            .get_mut()
+            .get_mut()
+            .shutdown_write()
+            .ok();
+
+        self.read_timeout.set_deadline(Some(DISCARD_TIMEOUT));
+        let mut input = self.next_header_source.by_ref().take(DISCARD_MAX_LEN);
+        io::copy(&mut input, &mut io::sink()).ok();
     }
 
     /// Reads the next line from self.next_header_source.
     ///
     /// Reads until `CRLF` is reached. The next read will start
     ///  at the first byte of the new line.
-    fn read_next_line(&mut self) -> IoResult<AsciiString> {
+    ///
+    /// Returns `None` if the line, `CRLF` excluded, is longer than `max_len` bytes. In that case
+    ///  the rest of the line is left unread.
+    #[allow(clippy::unbuffered_bytes)] // the source is a `BufReader`
+    fn read_next_line(&mut self, max_len: Option<usize>) -> IoResult<Option<AsciiString>> {
         let mut buf = Vec::new();
         let mut prev_byte_was_cr = false;
 
@@ -92,22 +164,42 @@ impl ClientConnection {
             if byte == b'\n' && prev_byte_was_cr {
                 buf.pop(); // removing the '\r'
                 return AsciiString::from_ascii(buf)
+                    .map(Some)
                     .map_err(|_| IoError::new(ErrorKind::InvalidInput, "Header is not in ASCII"));
             }
 
             prev_byte_was_cr = byte == b'\r';
 
             buf.push(byte);
+
+            // a trailing '\r' may still be the start of the `CRLF`
+            let line_len = buf.len() - prev_byte_was_cr as usize;
+            if max_len.map_or(false, |max_len| line_len > max_len) {
+                return Ok(None);
+            }
         }
     }
 
     /// Reads a request from the stream.
     /// Blocks until the header has been read.
     fn read(&mut self) -> Result<Request, ReadError> {
+        // the previous request's body must have been read before we can change the timeouts
+        self.next_header_source.wait_turn();
+        if self.first_request {
+            self.first_request = false;
+            self.read_timeout.await_first_request(self.timeouts.header);
+        } else {
+            self.read_timeout
+                .await_request(self.timeouts.keep_alive, self.timeouts.header);
+        }
+
         let (method, path, version, headers) = {
             // reading the request line
             let (method, path, version) = {
-                let line = self.read_next_line().map_err(ReadError::ReadIoError)?;
+                let line = self
+                    .read_next_line(self.limits.max_request_line)
+                    .map_err(ReadError::ReadIoError)?
+                    .ok_or(ReadError::RequestLineTooLong)?;
 
                 parse_request_line(
                     line.as_str().trim(), // TODO: remove this conversion
@@ -117,12 +209,33 @@ impl ClientConnection {
             // getting all headers
             let headers = {
                 let mut headers = Vec::new();
+                let mut remaining_bytes = self.limits.max_header_bytes;
                 loop {
-                    let line = self.read_next_line().map_err(ReadError::ReadIoError)?;
+                    let max_len = match (self.limits.max_header_line, remaining_bytes) {
+                        (Some(line), Some(remaining)) => Some(line.min(remaining)),
+                        (line, remaining) => line.or(remaining),
+                    };
+
+                    let line = self
+                        .read_next_line(max_len)
+                        .map_err(ReadError::ReadIoError)?
+                        .ok_or(ReadError::HeadersTooLarge(version.clone()))?;
 
                     if line.is_empty() {
                         break;
                     };
+
+                    if self
+                        .limits
+                        .max_headers
+                        .map_or(false, |max| headers.len() >= max)
+                    {
+                        return Err(ReadError::HeadersTooLarge(version));
+                    }
+                    if let Some(ref mut remaining) = remaining_bytes {
+                        // the `CRLF` counts towards the total size
+                        *remaining = remaining.saturating_sub(line.len() + 2);
+                    }
                     headers.push(match FromStr::from_str(line.as_str().trim()) {
                         // TODO: remove this conversion
                         Ok(h) => h,
@@ -136,6 +249,9 @@ impl ClientConnection {
             (method, path, version, headers)
         };
 
+        // the body is read with its own timeout
+        self.read_timeout.set_idle(self.timeouts.body);
+
         // building the writer for the request
         let writer = self.sink.next().unwrap();
 
@@ -153,6 +269,7 @@ impl ClientConnection {
             *self.remote_addr.as_ref().unwrap(),
             data_source,
             writer,
+            self.limits.max_body_size,
         )
         .map_err(|e| {
             use crate::request;
@@ -161,6 +278,9 @@ impl ClientConnection {
                 request::RequestCreationError::ExpectationFailed => {
                     ReadError::ExpectationFailed(version)
                 }
+                request::RequestCreationError::PayloadTooLarge => {
+                    ReadError::PayloadTooLarge(version)
+                }
             }
         })?;
 
@@ -183,6 +303,21 @@ impl Iterator for ClientConnection {
             return None;
         }
 
+        // the server is shutting down, no new request is accepted
+        if self.drain.is_draining() {
+            return None;
+        }
+
+        // the body of the previous request went over its limit, we don't know where the next
+        // request would start
+        self.next_header_source.wait_turn();
+        if let Some(flag) = self.body_too_large.take() {
+            if flag.load(Ordering::Acquire) {
+                self.discard_input();
+                return None;
+            }
+        }
+
         loop {
             let rq = match self.read() {
                 Err(ReadError::WrongRequestLine) => {
@@ -191,18 +326,46 @@ impl Iterator for ClientConnection {
                     response
                         .raw_print(writer, HTTPVersion(1, 1), &[], false, None)
                         .ok();
+                    self.discard_input();
                     return None; // we don't know where the next request would start,
                                  // se we have to close
                 }
 
+                Err(ReadError::RequestLineTooLong) => {
+                    let writer = self.sink.next().unwrap();
+                    let response = Response::new_empty(StatusCode(414));
+                    response
+                        .raw_print(writer, HTTPVersion(1, 1), &[], false, None)
+                        .ok();
+                    self.discard_input();
+                    return None; // the rest of the request line hasn't been read
+                }
+
+                Err(ReadError::HeadersTooLarge(ver)) => {
+                    let writer = self.sink.next().unwrap();
+                    let response = Response::new_empty(StatusCode(431));
+                    response.raw_print(writer, ver, &[], false, None).ok();
+                    self.discard_input();
+                    return None; // the rest of the headers hasn't been read
+                }
+
                 Err(ReadError::WrongHeader(ver)) => {
                     let writer = self.sink.next().unwrap();
                     let response = Response::new_empty(StatusCode(400));
                     response.raw_print(writer, ver, &[], false, None).ok();
+                    self.discard_input();
                     return None; // we don't know where the next request would start,
                                  // se we have to close
                 }
 
+                Err(ReadError::ReadIoError(ref err))
+                    if err.kind() == ErrorKind::TimedOut
+                        && self.read_timeout.is_awaiting_request() =>
+                {
+                    // keep-alive timeout, the client didn't start a new request
+                    return None;
+                }
+
                 Err(ReadError::ReadIoError(ref err)) if err.kind() == ErrorKind::TimedOut => {
                     // request timeout
                     let writer = self.sink.next().unwrap();
@@ -217,9 +380,18 @@ impl Iterator for ClientConnection {
                     let writer = self.sink.next().unwrap();
                     let response = Response::new_empty(StatusCode(417));
                     response.raw_print(writer, ver, &[], true, None).ok();
+                    self.discard_input();
                     return None; // TODO: should be recoverable, but needs handling in case of body
                 }
 
+                Err(ReadError::PayloadTooLarge(ver)) => {
+                    let writer = self.sink.next().unwrap();
+                    let response = Response::new_empty(StatusCode(413));
+                    response.raw_print(writer, ver, &[], false, None).ok();
+                    self.discard_input();
+                    return None; // the body hasn't been read
+                }
+
                 Err(ReadError::ReadIoError(_)) => return None,
 
                 Ok(rq) => rq,
@@ -259,7 +431,17 @@ impl Iterator for ClientConnection {
                 _ => (),
             };
 
+            // the response will close the connection
+            if self.drain.is_draining() {
+                self.no_more_requests = true;
+            }
+
+            self.body_too_large = Some(rq.body_too_large_flag());
+
             // returning the request
+            let rq = rq
+                .with_listen_addr(self.listen_addr.clone())
+                .with_drain(self.drain.clone());
             return Some(rq);
         }
     }
@@ -3,8 +3,11 @@
 #[cfg(unix)]
 use std::os::unix::net as unix_net;
 use std::{
+    fs::File,
+    io::Read,
     net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
     path::PathBuf,
+    time::Duration,
 };
 
 /// Unified listener. Either a [`TcpListener`] or [`std::os::unix::net::UnixListener`]
@@ -77,9 +80,35 @@ impl std::io::Write for Connection {
         }
     }
 }
+impl std::io::Read for &Connection {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Connection::Tcp(s) => (&*s).read(buf),
+            #[cfg(unix)]
+            Connection::Unix(s) => (&*s).read(buf),
+        }
+    }
+}
+impl std::io::Write for &Connection {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Connection::Tcp(s) => (&*s).write(buf),
+            #[cfg(unix)]
+            Connection::Unix(s) => (&*s).write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Connection::Tcp(s) => (&*s).flush(),
+            #[cfg(unix)]
+            Connection::Unix(s) => (&*s).flush(),
+        }
+    }
+}
 impl Connection {
     /// Gets the peer's address. Some for TCP, None for Unix sockets.
-    pub(crate) fn peer_addr(&mut self) -> std::io::Result<Option<SocketAddr>> {
+    pub(crate) fn peer_addr(&self) -> std::io::Result<Option<SocketAddr>> {
         match self {
             Self::Tcp(s) => s.peer_addr().map(Some),
             #[cfg(unix)]
@@ -95,6 +124,55 @@ impl Connection {
         }
     }
 
+    pub(crate) fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        match self {
+            Self::Tcp(s) => s.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Self::Unix(s) => s.set_read_timeout(timeout),
+        }
+    }
+
+    pub(crate) fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+        match self {
+            Self::Tcp(s) => s.set_nonblocking(nonblocking),
+            #[cfg(unix)]
+            Self::Unix(s) => s.set_nonblocking(nonblocking),
+        }
+    }
+
+    #[cfg(any(
+        feature = "ssl-openssl",
+        feature = "ssl-rustls",
+        feature = "ssl-native-tls"
+    ))]
+    pub(crate) fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        match self {
+            Self::Tcp(s) => s.set_write_timeout(timeout),
+            #[cfg(unix)]
+            Self::Unix(s) => s.set_write_timeout(timeout),
+        }
+    }
+
+    /// Writes `length` bytes of a file, starting at its current position, to the socket. On
+    /// Linux, the data is sent with `sendfile` instead of going through userspace.
+    pub(crate) fn send_file(&self, file: &File, length: u64) -> std::io::Result<()> {
+        #[cfg(target_os = "linux")]
+        let length = match self {
+            Self::Tcp(s) => crate::util::sendfile(file, s, length)?,
+            Self::Unix(s) => crate::util::sendfile(file, s, length)?,
+        };
+
+        let mut socket = self;
+        let copied = std::io::copy(&mut file.take(length), &mut socket)?;
+        if copied < length {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "The file is shorter than expected",
+            ));
+        }
+        Ok(())
+    }
+
     pub(crate) fn try_clone(&self) -> std::io::Result<Self> {
         match self {
             Self::Tcp(s) => s.try_clone().map(Self::from),
@@ -132,11 +210,19 @@ impl ConfigListenAddr {
         Self::Unix(path.into())
     }
 
-    pub(crate) fn bind(&self) -> std::io::Result<Listener> {
+    /// Binds a listener to each address, failing if any of them can't be bound.
+    pub(crate) fn bind(&self) -> std::io::Result<Vec<Listener>> {
         match self {
-            Self::IP(a) => TcpListener::bind(a.as_slice()).map(Listener::from),
+            Self::IP(a) if a.is_empty() => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "could not resolve to any addresses",
+            )),
+            Self::IP(a) => a
+                .iter()
+                .map(|addr| TcpListener::bind(addr).map(Listener::from))
+                .collect(),
             #[cfg(unix)]
-            Self::Unix(a) => unix_net::UnixListener::bind(a).map(Listener::from),
+            Self::Unix(a) => unix_net::UnixListener::bind(a).map(|l| vec![Listener::from(l)]),
         }
     }
 }
@@ -0,0 +1,76 @@
+//! Coordination between `Server::shutdown` and the client connections.
+
+use std::collections::HashMap;
+use std::net::Shutdown;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::connection::Connection;
+use crate::util::refined_tcp_stream::ReadTimeout;
+
+/// Shared between the server, its accept thread, the client connections and their requests.
+#[derive(Default)]
+pub(crate) struct Drain {
+    // true once the server has started shutting down
+    draining: AtomicBool,
+
+    // the connections currently open, with a handle to close them while they are idle
+    connections: Mutex<HashMap<usize, (Arc<Connection>, ReadTimeout)>>,
+
+    // identifier of the next connection to be tracked
+    next_id: AtomicUsize,
+}
+
+impl Drain {
+    /// Returns true if the server is shutting down. New requests must not be read, and
+    /// responses must close the connection.
+    pub(crate) fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn start(&self) {
+        self.draining.store(true, Ordering::Release);
+    }
+
+    /// Tracks a connection until the returned object is destroyed.
+    ///
+    /// `socket` must be a handle to the raw socket of the connection, so that it can be closed
+    /// even if a TLS stream wrapping it is currently blocked reading.
+    pub(crate) fn track(
+        self: &Arc<Self>,
+        socket: Arc<Connection>,
+        read_timeout: ReadTimeout,
+    ) -> Tracked {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.connections
+            .lock()
+            .unwrap()
+            .insert(id, (socket, read_timeout));
+
+        Tracked {
+            drain: self.clone(),
+            id,
+        }
+    }
+
+    /// Closes the reading side of the connections that are waiting for a new request.
+    pub(crate) fn close_idle_connections(&self) {
+        for (socket, read_timeout) in self.connections.lock().unwrap().values() {
+            if read_timeout.is_idle() {
+                socket.shutdown(Shutdown::Read).ok();
+            }
+        }
+    }
+}
+
+/// Removes a connection from its `Drain` on destruction.
+pub(crate) struct Tracked {
+    drain: Arc<Drain>,
+    id: usize,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.drain.connections.lock().unwrap().remove(&self.id);
+    }
+}
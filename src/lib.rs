@@ -91,44 +91,51 @@
 #![deny(rust_2018_idioms)]
 #![allow(clippy::match_like_matches_macro)]
 
-#[cfg(any(
-    feature = "ssl-openssl",
-    feature = "ssl-rustls",
-    feature = "ssl-native-tls"
-))]
-use zeroize::Zeroizing;
-
+use std::collections::HashMap;
 use std::error::Error;
 use std::io::Error as IoError;
 use std::io::ErrorKind as IoErrorKind;
+use std::io::Read;
 use std::io::Result as IoResult;
 use std::net::{Shutdown, TcpStream, ToSocketAddrs};
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering::Relaxed;
-use std::sync::mpsc;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use client::ClientConnection;
 use connection::Connection;
+use drain::Drain;
+use stats::Counters;
 use util::MessagesQueue;
 
 pub use common::{HTTPVersion, Header, HeaderField, Method, StatusCode};
 pub use connection::{ConfigListenAddr, ListenAddr, Listener};
 pub use request::{ReadWrite, Request};
 pub use response::{Response, ResponseBox};
+pub use sse::{SseEvent, SseSender};
+pub use ssl::{TlsInfo, TlsVersion};
+pub use stats::ServerStats;
+pub use streaming::StreamingResponse;
 pub use test::TestRequest;
 
 mod client;
 mod common;
 mod connection;
+mod drain;
 mod log;
 mod request;
 mod response;
+mod sse;
 mod ssl;
+pub mod static_files;
+mod stats;
+mod streaming;
 mod test;
 mod util;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
 /// The main class of this library.
 ///
@@ -143,10 +150,21 @@ pub struct Server {
     // queue for messages received by child threads
     messages: Arc<MessagesQueue<Message>>,
 
-    // result of TcpListener::local_addr()
-    listening_addr: ListenAddr,
+    // activity counters, updated by the accept thread and the client connections
+    counters: Arc<Counters>,
+
+    // state of the graceful shutdown, shared with the client connections
+    drain: Arc<Drain>,
+
+    // result of Listener::local_addr() for each listener, the first one being the main one
+    listening_addrs: Vec<ListenAddr>,
+
+    // SSL context of each listener, in the order of `listening_addrs`, shared with its accept
+    // thread
+    ssl_contexts: Vec<Option<SharedSslContext>>,
 }
 
+#[allow(clippy::large_enum_variant)] // nearly all the messages are requests
 enum Message {
     Error(IoError),
     NewRequest(Request),
@@ -164,8 +182,243 @@ impl From<Request> for Message {
     }
 }
 
+/// Settings of the server that don't depend on the listener.
+#[derive(Clone, Copy, Default)]
+struct Settings {
+    timeouts: Timeouts,
+    limits: Limits,
+    workers: Workers,
+    request_queue: RequestQueue,
+}
+
+// building the SSL capabilities
+#[cfg(any(
+    all(feature = "ssl-openssl", feature = "ssl-rustls"),
+    all(feature = "ssl-openssl", feature = "ssl-native-tls"),
+    all(feature = "ssl-native-tls", feature = "ssl-rustls"),
+))]
+compile_error!(
+    "Only one feature from 'ssl-openssl', 'ssl-rustls', 'ssl-native-tls' can be enabled at the same time"
+);
+#[cfg(not(any(
+    feature = "ssl-openssl",
+    feature = "ssl-rustls",
+    feature = "ssl-native-tls"
+)))]
+type SslContext = ();
+#[cfg(any(
+    feature = "ssl-openssl",
+    feature = "ssl-rustls",
+    feature = "ssl-native-tls"
+))]
+type SslContext = crate::ssl::SslContextImpl;
+
+/// SSL context of a listener, which `Server::reload_tls` can replace while the server runs.
+///
+/// Each connection keeps the context it was accepted with.
+type SharedSslContext = Arc<RwLock<Arc<SslContext>>>;
+
+fn build_ssl_context(
+    ssl_config: Option<SslConfig>,
+) -> Result<Option<SslContext>, Box<dyn Error + Send + Sync + 'static>> {
+    match ssl_config {
+        #[cfg(any(
+            feature = "ssl-openssl",
+            feature = "ssl-rustls",
+            feature = "ssl-native-tls"
+        ))]
+        Some(config) => Ok(Some(SslContext::from_config(config)?)),
+        #[cfg(not(any(
+            feature = "ssl-openssl",
+            feature = "ssl-rustls",
+            feature = "ssl-native-tls"
+        )))]
+        Some(_) => Err(
+            "Building a server with SSL requires enabling the `ssl` feature in tiny-http".into(),
+        ),
+        None => Ok(None),
+    }
+}
+
+/// Everything an accept thread and the connections it accepts share with the server.
+#[derive(Clone)]
+struct AcceptContext {
+    close: Arc<AtomicBool>,
+    messages: Arc<MessagesQueue<Message>>,
+    counters: Arc<Counters>,
+    drain: Arc<Drain>,
+    tasks_pool: Arc<util::TaskPool>,
+    settings: Settings,
+}
+
+/// Body of the accept thread of a listener.
+fn accept_connections(
+    server: Listener,
+    listen_addr: ListenAddr,
+    ssl: Option<SharedSslContext>,
+    context: AcceptContext,
+) {
+    log::debug!("Running accept thread for {}", listen_addr);
+    while !context.close.load(Relaxed) {
+        let mut sock = match server.accept() {
+            Ok((sock, _)) => sock,
+            Err(e) => {
+                log::error!("Error accepting new client: {}", e);
+                context.messages.push(e.into());
+                break;
+            }
+        };
+
+        let rejection = if context.tasks_pool.is_saturated() {
+            context.settings.workers.overload.rejection()
+        } else {
+            None
+        };
+        if let Some(response) = rejection {
+            // no response can be sent before the TLS handshake, the connection is just closed
+            if ssl.is_none() {
+                let version = HTTPVersion(1, 1);
+                response
+                    .raw_print(&mut sock, version, &[], false, None)
+                    .ok();
+                sock.shutdown(Shutdown::Write).ok();
+
+                // the accept thread can't wait for the client, so only the data that has
+                // already been received is discarded before closing
+                if sock.set_nonblocking(true).is_ok() {
+                    let mut input = (&sock).take(client::DISCARD_MAX_LEN);
+                    std::io::copy(&mut input, &mut std::io::sink()).ok();
+                }
+            }
+            continue;
+        }
+
+        let mut connection = Some((sock, context.counters.connection()));
+        let listen_addr = listen_addr.clone();
+        let ssl = ssl.as_ref().map(|ssl| ssl.read().unwrap().clone());
+        let context = context.clone();
+        context.tasks_pool.clone().spawn(Box::new(move || {
+            if let Some((sock, _registration)) = connection.take() {
+                handle_connection(sock, listen_addr.clone(), ssl.as_deref(), &context);
+            }
+        }));
+    }
+    log::debug!("Terminating accept thread for {}", listen_addr);
+}
+
+/// Runs on a worker thread for the whole life of a connection.
+fn handle_connection(
+    sock: Connection,
+    listen_addr: ListenAddr,
+    ssl: Option<&SslContext>,
+    context: &AcceptContext,
+) {
+    use util::RefinedTcpStream;
+
+    let Settings {
+        timeouts,
+        limits,
+        request_queue,
+        ..
+    } = context.settings;
+
+    // kept to close the connection during a graceful shutdown, and to send file responses
+    // directly when there is no SSL layer in between
+    let raw_socket = match sock.try_clone() {
+        Ok(s) => Arc::new(s),
+        Err(_) => return,
+    };
+
+    let ((read_closable, write_closable), peer_certificates, tls_info) = match ssl {
+        None => (RefinedTcpStream::new(sock), None, None),
+        #[cfg(any(
+            feature = "ssl-openssl",
+            feature = "ssl-rustls",
+            feature = "ssl-native-tls"
+        ))]
+        Some(ssl) => {
+            // trying to apply SSL over the connection
+            // if an error occurs, we just close the socket
+            let deadline = timeouts
+                .tls_handshake
+                .map(|timeout| Instant::now() + timeout);
+            let sock = match ssl.accept(sock, deadline) {
+                Ok(s) => s,
+                Err(err) => {
+                    log::warn!(
+                        "TLS handshake on {} with {:?} failed: {}",
+                        listen_addr,
+                        raw_socket.peer_addr(),
+                        err
+                    );
+                    return;
+                }
+            };
+
+            raw_socket.set_read_timeout(None).ok();
+            raw_socket.set_write_timeout(None).ok();
+            let peer_certificates = sock.peer_certificates().map(Arc::new);
+            let tls_info = sock.tls_info().map(Arc::new);
+            (RefinedTcpStream::new(sock), peer_certificates, tls_info)
+        }
+        #[cfg(not(any(
+            feature = "ssl-openssl",
+            feature = "ssl-rustls",
+            feature = "ssl-native-tls"
+        )))]
+        Some(_) => unreachable!(),
+    };
+
+    let _tracked = context
+        .drain
+        .track(raw_socket.clone(), read_closable.read_timeout());
+    let mut client = ClientConnection::new(
+        write_closable,
+        read_closable,
+        listen_addr,
+        timeouts,
+        limits,
+        context.drain.clone(),
+    );
+
+    let messages = &context.messages;
+    let counters = &context.counters;
+
+    while let Some(rq) = client.next() {
+        let rq = rq
+            .with_registration(counters.queued_request())
+            .with_peer_certificates(peer_certificates.clone())
+            .with_tls_info(tls_info.clone())
+            .with_socket(raw_socket.clone());
+        if !enqueue(messages, request_queue.overload, rq) {
+            // the rejection closes the connection
+            client.discard_input();
+            break;
+        }
+    }
+}
+
+/// Pushes a request read from a client to the messages queue.
+///
+/// Returns false if the request was rejected.
+fn enqueue(messages: &MessagesQueue<Message>, overload: Overload, rq: Request) -> bool {
+    match overload {
+        Overload::Wait => messages.push_wait(rq.into()),
+        Overload::Reject { .. } => {
+            if let Err(Message::NewRequest(rq)) = messages.try_push(rq.into()) {
+                if let Some(response) = overload.rejection() {
+                    rq.respond(response).ok();
+                }
+                return false;
+            }
+        }
+    }
+    true
+}
+
 // this trait is to make sure that Server implements Share and Send
 #[doc(hidden)]
+#[allow(dead_code)]
 trait MustBeShareDummy: Sync + Send {}
 #[doc(hidden)]
 impl MustBeShareDummy for Server {}
@@ -175,38 +428,375 @@ pub struct IncomingRequests<'a> {
 }
 
 /// Represents the parameters required to create a server.
+///
+/// New settings may be added in future versions, so a configuration is built with
+/// [`ServerConfig::new`] and then adjusted field by field.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct ServerConfig {
-    /// The addresses to try to listen to.
+    /// The addresses to listen to, with a listener for each of them.
     pub addr: ConfigListenAddr,
 
-    /// If `Some`, then the server will use SSL to encode the communications.
+    /// Other addresses to listen to, in addition to `addr`, each with its own SSL
+    /// configuration.
+    pub additional_listeners: Vec<ListenerConfig>,
+
+    /// If `Some`, then the server will use SSL to encode the communications on `addr`.
     pub ssl: Option<SslConfig>,
+
+    /// Timeouts applied when reading from the clients.
+    pub timeouts: Timeouts,
+
+    /// Limits on the size of the requests sent by the clients.
+    pub limits: Limits,
+
+    /// Configuration of the threads that handle the client connections.
+    pub workers: Workers,
+
+    /// Configuration of the queue of requests waiting to be returned by `recv()` and friends.
+    pub request_queue: RequestQueue,
 }
 
-/// Configuration of the server for SSL.
+impl ServerConfig {
+    /// Listens on `addr` without SSL, with the default settings.
+    pub fn new(addr: ConfigListenAddr) -> ServerConfig {
+        ServerConfig {
+            addr,
+            additional_listeners: Vec::new(),
+            ssl: None,
+            timeouts: Timeouts::default(),
+            limits: Limits::default(),
+            workers: Workers::default(),
+            request_queue: RequestQueue::default(),
+        }
+    }
+}
+
+/// An additional address for a server to listen to.
+///
+/// This makes it possible for a single server to accept plain HTTP connections on one socket
+/// and HTTPS connections on another one. Use [`Request::secure`] to tell them apart.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ListenerConfig {
+    /// The addresses to listen to, with a listener for each of them sharing `ssl`.
+    pub addr: ConfigListenAddr,
+
+    /// If `Some`, then the connections accepted on this address use SSL.
+    pub ssl: Option<SslConfig>,
+}
+
+impl ListenerConfig {
+    /// Listens on `addr`, with SSL if `ssl` is `Some`.
+    pub fn new(addr: ConfigListenAddr, ssl: Option<SslConfig>) -> ListenerConfig {
+        ListenerConfig { addr, ssl }
+    }
+}
+
+/// Timeouts applied when reading from the clients.
+///
+/// A client that doesn't send its data in time receives a `408 Request Timeout` response and
+/// is disconnected. All the timeouts are disabled by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Timeouts {
+    /// Maximum time allowed to receive the request line and headers of a request.
+    ///
+    /// For the first request of a connection this starts when the connection is accepted,
+    /// for the following ones when their first byte is received.
+    pub header: Option<Duration>,
+
+    /// Maximum time a connection may stay idle between two requests.
+    ///
+    /// Connections that exceed it are closed without a response.
+    pub keep_alive: Option<Duration>,
+
+    /// Maximum time a single read of a request body may block.
+    ///
+    /// When it expires, reading from [`Request::as_reader`] returns an error.
+    pub body: Option<Duration>,
+
+    /// Maximum time allowed to complete the TLS handshake of an HTTPS connection, starting
+    /// when the connection is accepted.
+    ///
+    /// When it expires, the handshake fails and the connection is closed without a response.
+    pub tls_handshake: Option<Duration>,
+}
+
+/// Limits on the size of the requests sent by the clients.
+///
+/// A request line that is too long is answered with `414 URI Too Long`, and headers that are
+/// too large with `431 Request Header Fields Too Large`. The connection is then closed.
+///
+/// `None` disables a limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Limits {
+    /// Maximum length in bytes of the request line (e.g. `GET /index.html HTTP/1.1`).
+    ///
+    /// Defaults to 8 KiB.
+    pub max_request_line: Option<usize>,
+
+    /// Maximum length in bytes of a single header line.
+    ///
+    /// Defaults to 8 KiB.
+    pub max_header_line: Option<usize>,
+
+    /// Maximum length in bytes of all the header lines of a request, line endings included.
+    ///
+    /// Defaults to 64 KiB.
+    pub max_header_bytes: Option<usize>,
+
+    /// Maximum number of headers in a request.
+    ///
+    /// Defaults to 100.
+    pub max_headers: Option<usize>,
+
+    /// Maximum size in bytes of the body of a request.
+    ///
+    /// Requests announcing a larger `Content-Length` are answered with `413 Payload Too Large`.
+    /// Reading a chunked body fails once it crosses the limit. Individual requests can be given
+    /// a lower limit with [`Request::set_body_limit`].
+    ///
+    /// Defaults to `None`.
+    pub max_body_size: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_request_line: Some(8 * 1024),
+            max_header_line: Some(8 * 1024),
+            max_header_bytes: Some(64 * 1024),
+            max_headers: Some(100),
+            max_body_size: None,
+        }
+    }
+}
+
+/// Configuration of the threads that handle the client connections.
+///
+/// Each connection is handled by a worker thread for as long as it stays open. A new worker
+/// is started whenever all the existing ones are busy, up to `max`. Once `max` is reached, new
+/// connections wait in a queue of `accept_queue` entries, and when this queue is full too,
+/// `overload` decides what happens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Workers {
+    /// Number of worker threads that are kept alive even when they are idle.
+    ///
+    /// Defaults to 4.
+    pub min: usize,
+
+    /// Maximum number of worker threads, `None` for no limit.
+    ///
+    /// Since a keep-alive connection holds its worker until it is closed, this also bounds the
+    /// number of connections handled at the same time. A limit should therefore come with
+    /// [`Timeouts::header`] and [`Timeouts::keep_alive`], otherwise a few idle connections are
+    /// enough to keep the server from answering anyone else.
+    ///
+    /// Defaults to `None`.
+    pub max: Option<usize>,
+
+    /// Maximum number of connections waiting for a worker once `max` workers are busy.
+    ///
+    /// Defaults to 64.
+    pub accept_queue: usize,
+
+    /// Time after which an idle worker above `min` stops.
+    ///
+    /// Defaults to 5 seconds.
+    pub idle_timeout: Duration,
+
+    /// What to do with new connections when all the workers are busy and the accept queue
+    /// is full.
+    ///
+    /// Defaults to [`Overload::Wait`].
+    pub overload: Overload,
+}
+
+impl Default for Workers {
+    fn default() -> Self {
+        Workers {
+            min: 4,
+            max: None,
+            accept_queue: 64,
+            idle_timeout: Duration::from_secs(5),
+            overload: Overload::Wait,
+        }
+    }
+}
+
+/// Configuration of the queue of requests waiting to be returned by `recv()` and friends.
+///
+/// The requests read from the clients are stored in this queue until the application picks
+/// them up. Bounding it keeps the memory usage predictable when the application falls behind.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RequestQueue {
+    /// Maximum number of requests in the queue, `None` for no limit.
+    ///
+    /// Defaults to `None`.
+    pub max_len: Option<usize>,
+
+    /// What to do with new requests when the queue is full.
+    ///
+    /// Defaults to [`Overload::Wait`].
+    pub overload: Overload,
+}
+
+/// What the server does when it can't keep up with the clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overload {
+    /// Wait until there is room.
+    ///
+    /// For new connections, this means that they are left in the backlog of the listening
+    /// socket until a worker becomes available. For new requests, this means that the server
+    /// stops reading from the client until the queue has room.
+    Wait,
+
+    /// Answer `503 Service Unavailable` with a `Retry-After` header and close the connection.
+    Reject {
+        /// Value of the `Retry-After` header, rounded up to the second.
+        retry_after: Duration,
+    },
+}
+
+impl Default for Overload {
+    fn default() -> Self {
+        Overload::Wait
+    }
+}
+
+impl Overload {
+    /// Builds the response sent to the clients that are rejected, if any.
+    fn rejection(&self) -> Option<Response<std::io::Empty>> {
+        match *self {
+            Overload::Wait => None,
+            Overload::Reject { retry_after } => {
+                let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                let header = Header::from_bytes(&b"Retry-After"[..], secs.to_string()).unwrap();
+                let response = Response::empty(StatusCode(503)).with_header(header);
+                Some(response.with_connection_close())
+            }
+        }
+    }
+}
+
+/// Configuration of the server for SSL.
+///
+/// Certificates and private keys can be given in the PEM or the DER format. Private keys can be
+/// PKCS#8, RSA (PKCS#1) or EC (SEC1) keys, but can't be encrypted. The `ssl-native-tls`
+/// implementation only supports PKCS#8 keys in the PEM format.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
 pub struct SslConfig {
     /// Contains the public certificate to send to clients.
     pub certificate: Vec<u8>,
     /// Contains the ultra-secret private key used to decode communications.
     pub private_key: Vec<u8>,
+    /// Certificates to send instead of `certificate` to the clients that ask for a specific
+    /// server name through SNI.
+    ///
+    /// The keys are host names such as `example.com`, compared case-insensitively. Clients that
+    /// don't use SNI, or ask for a name that isn't in the map, receive `certificate`.
+    ///
+    /// This isn't supported by the `ssl-native-tls` implementation. Defaults to an empty map.
+    pub server_names: HashMap<String, SslCertificate>,
+    /// If `Some`, the clients are asked for a certificate during the handshake, which is then
+    /// available through [`Request::peer_certificates`].
+    ///
+    /// This isn't supported by the `ssl-native-tls` implementation. Defaults to `None`.
+    pub client_auth: Option<ClientAuth>,
+    /// Protocols that the server supports for ALPN, by order of preference, such as `b"h2"`
+    /// or `b"http/1.1"`.
+    ///
+    /// The protocol chosen for a connection is available through [`Request::tls_info`].
+    /// This isn't supported by the `ssl-native-tls` implementation. Defaults to an empty list.
+    pub alpn_protocols: Vec<Vec<u8>>,
+    /// Oldest version of the protocol that the clients may use.
+    ///
+    /// Defaults to `None`, which keeps the default of the SSL implementation.
+    pub min_version: Option<TlsVersion>,
+    /// Most recent version of the protocol that the clients may use.
+    ///
+    /// Defaults to `None`, which keeps the default of the SSL implementation.
+    pub max_version: Option<TlsVersion>,
+    /// Cipher suites that the clients may use, by order of preference.
+    ///
+    /// The names are those of the SSL implementation, for instance `ECDHE-RSA-AES128-GCM-SHA256`
+    /// with OpenSSL and `TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256` with Rustls. The TLS 1.3 suites
+    /// can be given with their standard name, such as `TLS_AES_128_GCM_SHA256`, for both.
+    /// With OpenSSL, the TLS 1.2 and TLS 1.3 suites are configured separately, and a version for
+    /// which no suite is given keeps its default ones.
+    ///
+    /// This isn't supported by the `ssl-native-tls` implementation. Defaults to an empty list,
+    /// which keeps the default of the SSL implementation.
+    pub cipher_suites: Vec<String>,
+}
+
+impl SslConfig {
+    /// Uses `certificate` and `private_key` for all the clients, with the default settings.
+    pub fn new(certificate: Vec<u8>, private_key: Vec<u8>) -> SslConfig {
+        SslConfig {
+            certificate,
+            private_key,
+            ..Default::default()
+        }
+    }
+}
+
+/// Authentication of the clients with certificates, also known as mutual TLS.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ClientAuth {
+    /// Certificates of the authorities trusted to sign the client certificates, in the PEM
+    /// format.
+    pub ca_certificates: Vec<u8>,
+    /// If true, the clients that don't send a certificate are rejected during the handshake.
+    /// Otherwise they can connect anyway, and `Request::peer_certificates` returns `None`.
+    ///
+    /// The clients that send a certificate which isn't signed by a trusted authority are always
+    /// rejected.
+    pub required: bool,
+}
+
+impl ClientAuth {
+    /// Trusts the authorities of `ca_certificates`, requiring a certificate if `required`.
+    pub fn new(ca_certificates: Vec<u8>, required: bool) -> ClientAuth {
+        ClientAuth {
+            ca_certificates,
+            required,
+        }
+    }
+}
+
+/// A certificate chain and its private key, in the PEM format.
+#[derive(Debug, Clone)]
+pub struct SslCertificate {
+    /// Contains the public certificate to send to clients, followed by its chain.
+    pub certificate: Vec<u8>,
+    /// Contains the private key of the certificate.
+    pub private_key: Vec<u8>,
 }
 
 impl Server {
     /// Shortcut for a simple server on a specific address.
+    ///
+    /// The server listens on every address that `addr` resolves to.
     #[inline]
     pub fn http<A>(addr: A) -> Result<Server, Box<dyn Error + Send + Sync + 'static>>
     where
         A: ToSocketAddrs,
     {
-        Server::new(ServerConfig {
-            addr: ConfigListenAddr::from_socket_addrs(addr)?,
-            ssl: None,
-        })
+        Server::new(ServerConfig::new(ConfigListenAddr::from_socket_addrs(
+            addr,
+        )?))
     }
 
     /// Shortcut for an HTTPS server on a specific address.
+    ///
+    /// The server listens on every address that `addr` resolves to.
     #[cfg(any(
         feature = "ssl-openssl",
         feature = "ssl-rustls",
@@ -220,10 +810,9 @@ impl Server {
     where
         A: ToSocketAddrs,
     {
-        Server::new(ServerConfig {
-            addr: ConfigListenAddr::from_socket_addrs(addr)?,
-            ssl: Some(config),
-        })
+        let mut server_config = ServerConfig::new(ConfigListenAddr::from_socket_addrs(addr)?);
+        server_config.ssl = Some(config);
+        Server::new(server_config)
     }
 
     #[cfg(unix)]
@@ -232,16 +821,35 @@ impl Server {
     pub fn http_unix(
         path: &std::path::Path,
     ) -> Result<Server, Box<dyn Error + Send + Sync + 'static>> {
-        Server::new(ServerConfig {
-            addr: ConfigListenAddr::unix_from_path(path),
-            ssl: None,
-        })
+        Server::new(ServerConfig::new(ConfigListenAddr::unix_from_path(path)))
     }
 
-    /// Builds a new server that listens on the specified address.
+    /// Builds a new server that listens on the specified addresses.
     pub fn new(config: ServerConfig) -> Result<Server, Box<dyn Error + Send + Sync + 'static>> {
-        let listener = config.addr.bind()?;
-        Self::from_listener(listener, config.ssl)
+        let mut configs = vec![(config.addr, config.ssl)];
+        configs.extend(
+            config
+                .additional_listeners
+                .into_iter()
+                .map(|listener| (listener.addr, listener.ssl)),
+        );
+
+        let mut listeners = Vec::new();
+        for (addr, ssl) in configs {
+            for listener in addr.bind()? {
+                listeners.push((listener, ssl.clone()));
+            }
+        }
+
+        Self::from_listeners_impl(
+            listeners,
+            Settings {
+                timeouts: config.timeouts,
+                limits: config.limits,
+                workers: config.workers,
+                request_queue: config.request_queue,
+            },
+        )
     }
 
     /// Builds a new server using the specified TCP listener.
@@ -252,144 +860,71 @@ impl Server {
         listener: L,
         ssl_config: Option<SslConfig>,
     ) -> Result<Server, Box<dyn Error + Send + Sync + 'static>> {
-        let listener = listener.into();
+        Self::from_listeners(vec![(listener.into(), ssl_config)])
+    }
+
+    /// Same as `from_listener()`, but listens on several sockets at once, each with its own
+    /// SSL configuration.
+    ///
+    /// The first listener is the one returned by `server_addr()`.
+    pub fn from_listeners(
+        listeners: Vec<(Listener, Option<SslConfig>)>,
+    ) -> Result<Server, Box<dyn Error + Send + Sync + 'static>> {
+        if listeners.is_empty() {
+            return Err("A server needs at least one listener".into());
+        }
+
+        Self::from_listeners_impl(listeners, Settings::default())
+    }
+
+    fn from_listeners_impl(
+        listeners: Vec<(Listener, Option<SslConfig>)>,
+        settings: Settings,
+    ) -> Result<Server, Box<dyn Error + Send + Sync + 'static>> {
         // building the "close" variable
         let close_trigger = Arc::new(AtomicBool::new(false));
 
-        // building the TcpListener
-        let (server, local_addr) = {
+        // building the SSL capabilities of each listener
+        let mut accepting = Vec::with_capacity(listeners.len());
+        for (listener, ssl_config) in listeners {
             let local_addr = listener.local_addr()?;
             log::debug!("Server listening on {}", local_addr);
-            (listener, local_addr)
-        };
-
-        // building the SSL capabilities
-        #[cfg(any(
-            all(feature = "ssl-openssl", feature = "ssl-rustls"),
-            all(feature = "ssl-openssl", feature = "ssl-native-tls"),
-            all(feature = "ssl-native-tls", feature = "ssl-rustls"),
-        ))]
-        compile_error!(
-            "Only one feature from 'ssl-openssl', 'ssl-rustls', 'ssl-native-tls' can be enabled at the same time"
-        );
-        #[cfg(not(any(
-            feature = "ssl-openssl",
-            feature = "ssl-rustls",
-            feature = "ssl-native-tls"
-        )))]
-        type SslContext = ();
-        #[cfg(any(
-            feature = "ssl-openssl",
-            feature = "ssl-rustls",
-            feature = "ssl-native-tls"
-        ))]
-        type SslContext = crate::ssl::SslContextImpl;
-        let ssl: Option<SslContext> = {
-            match ssl_config {
-                #[cfg(any(
-                    feature = "ssl-openssl",
-                    feature = "ssl-rustls",
-                    feature = "ssl-native-tls"
-                ))]
-                Some(config) => Some(SslContext::from_pem(
-                    config.certificate,
-                    Zeroizing::new(config.private_key),
-                )?),
-                #[cfg(not(any(
-                    feature = "ssl-openssl",
-                    feature = "ssl-rustls",
-                    feature = "ssl-native-tls"
-                )))]
-                Some(_) => return Err(
-                    "Building a server with SSL requires enabling the `ssl` feature in tiny-http"
-                        .into(),
-                ),
-                None => None,
-            }
-        };
+            let ssl =
+                build_ssl_context(ssl_config)?.map(|ssl| Arc::new(RwLock::new(Arc::new(ssl))));
+            accepting.push((listener, local_addr, ssl));
+        }
+        let listening_addrs = accepting.iter().map(|(_, addr, _)| addr.clone()).collect();
+        let ssl_contexts = accepting.iter().map(|(_, _, ssl)| ssl.clone()).collect();
 
-        // creating a task where server.accept() is continuously called
+        // creating a task per listener where accept() is continuously called
         // and ClientConnection objects are pushed in the messages queue
-        let messages = MessagesQueue::with_capacity(8);
-
-        let inside_close_trigger = close_trigger.clone();
-        let inside_messages = messages.clone();
-        thread::spawn(move || {
-            // a tasks pool is used to dispatch the connections into threads
-            let tasks_pool = util::TaskPool::new();
-
-            log::debug!("Running accept thread");
-            while !inside_close_trigger.load(Relaxed) {
-                let new_client = match server.accept() {
-                    Ok((sock, _)) => {
-                        use util::RefinedTcpStream;
-                        let (read_closable, write_closable) = match ssl {
-                            None => RefinedTcpStream::new(sock),
-                            #[cfg(any(
-                                feature = "ssl-openssl",
-                                feature = "ssl-rustls",
-                                feature = "ssl-native-tls"
-                            ))]
-                            Some(ref ssl) => {
-                                // trying to apply SSL over the connection
-                                // if an error occurs, we just close the socket and resume listening
-                                let sock = match ssl.accept(sock) {
-                                    Ok(s) => s,
-                                    Err(_) => continue,
-                                };
-
-                                RefinedTcpStream::new(sock)
-                            }
-                            #[cfg(not(any(
-                                feature = "ssl-openssl",
-                                feature = "ssl-rustls",
-                                feature = "ssl-native-tls"
-                            )))]
-                            Some(ref _ssl) => unreachable!(),
-                        };
-
-                        Ok(ClientConnection::new(write_closable, read_closable))
-                    }
-                    Err(e) => Err(e),
-                };
-
-                match new_client {
-                    Ok(client) => {
-                        let messages = inside_messages.clone();
-                        let mut client = Some(client);
-                        tasks_pool.spawn(Box::new(move || {
-                            if let Some(client) = client.take() {
-                                // Synchronization is needed for HTTPS requests to avoid a deadlock
-                                if client.secure() {
-                                    let (sender, receiver) = mpsc::channel();
-                                    for rq in client {
-                                        messages.push(rq.with_notify_sender(sender.clone()).into());
-                                        receiver.recv().unwrap();
-                                    }
-                                } else {
-                                    for rq in client {
-                                        messages.push(rq.into());
-                                    }
-                                }
-                            }
-                        }));
-                    }
+        let messages = MessagesQueue::new(settings.request_queue.max_len);
+        let counters = Arc::new(Counters::default());
+        let drain = Arc::new(Drain::default());
 
-                    Err(e) => {
-                        log::error!("Error accepting new client: {}", e);
-                        inside_messages.push(e.into());
-                        break;
-                    }
-                }
-            }
-            log::debug!("Terminating accept thread");
-        });
+        // a tasks pool is used to dispatch the connections into threads
+        let tasks_pool = Arc::new(util::TaskPool::new(&settings.workers));
+
+        for (listener, local_addr, ssl) in accepting {
+            let context = AcceptContext {
+                close: close_trigger.clone(),
+                messages: messages.clone(),
+                counters: counters.clone(),
+                drain: drain.clone(),
+                tasks_pool: tasks_pool.clone(),
+                settings,
+            };
+            thread::spawn(move || accept_connections(listener, local_addr, ssl, context));
+        }
 
         // result
         Ok(Server {
             messages,
+            counters,
+            drain,
             close: close_trigger,
-            listening_addr: local_addr,
+            listening_addrs,
+            ssl_contexts,
         })
     }
 
@@ -402,22 +937,74 @@ impl Server {
     }
 
     /// Returns the address the server is listening to.
+    ///
+    /// If the server listens on several addresses, this is the first one.
     #[inline]
     pub fn server_addr(&self) -> ListenAddr {
-        self.listening_addr.clone()
+        self.listening_addrs[0].clone()
+    }
+
+    /// Returns all the addresses the server is listening to.
+    ///
+    /// They come in the order of the configuration: the addresses of `ServerConfig::addr`,
+    /// then those of each additional listener.
+    pub fn server_addrs(&self) -> Vec<ListenAddr> {
+        self.listening_addrs.clone()
+    }
+
+    /// Replaces the SSL configuration of an HTTPS listener, for instance to renew a certificate
+    /// before it expires.
+    ///
+    /// `listener` is the index of the listener in the list returned by `server_addrs()`. The
+    /// other listeners keep their own configuration, including those bound to the other
+    /// addresses of the same `ConfigListenAddr`.
+    ///
+    /// The new configuration is used for the connections accepted from now on, while the
+    /// connections that are already open keep using the previous one.
+    ///
+    /// Returns an error, and keeps the current configuration, if `config` is invalid or if the
+    /// listener doesn't exist or doesn't use SSL.
+    pub fn reload_tls(
+        &self,
+        listener: usize,
+        config: SslConfig,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        let context = match self.ssl_contexts.get(listener) {
+            Some(Some(context)) => context,
+            Some(None) => return Err("This listener doesn't use SSL".into()),
+            None => return Err("The server doesn't have this listener".into()),
+        };
+
+        let ssl = match build_ssl_context(Some(config))? {
+            Some(ssl) => Arc::new(ssl),
+            None => unreachable!(),
+        };
+        *context.write().unwrap() = ssl;
+        Ok(())
     }
 
     /// Returns the number of clients currently connected to the server.
     pub fn num_connections(&self) -> usize {
-        unimplemented!()
-        //self.requests_receiver.lock().len()
+        self.counters.snapshot().connections
+    }
+
+    /// Returns the number of requests waiting to be returned by `recv()` and friends.
+    pub fn queue_len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Returns a snapshot of the current activity of the server.
+    ///
+    /// See [`ServerStats`] for the meaning of each value.
+    pub fn stats(&self) -> ServerStats {
+        self.counters.snapshot()
     }
 
     /// Blocks until an HTTP request has been submitted and returns it.
     pub fn recv(&self) -> IoResult<Request> {
         match self.messages.pop() {
             Some(Message::Error(err)) => Err(err),
-            Some(Message::NewRequest(rq)) => Ok(rq),
+            Some(Message::NewRequest(rq)) => Ok(self.received(rq)),
             None => Err(IoError::new(IoErrorKind::Other, "thread unblocked")),
         }
     }
@@ -426,7 +1013,7 @@ impl Server {
     pub fn recv_timeout(&self, timeout: Duration) -> IoResult<Option<Request>> {
         match self.messages.pop_timeout(timeout) {
             Some(Message::Error(err)) => Err(err),
-            Some(Message::NewRequest(rq)) => Ok(Some(rq)),
+            Some(Message::NewRequest(rq)) => Ok(Some(self.received(rq))),
             None => Ok(None),
         }
     }
@@ -435,11 +1022,78 @@ impl Server {
     pub fn try_recv(&self) -> IoResult<Option<Request>> {
         match self.messages.try_pop() {
             Some(Message::Error(err)) => Err(err),
-            Some(Message::NewRequest(rq)) => Ok(Some(rq)),
+            Some(Message::NewRequest(rq)) => Ok(Some(self.received(rq))),
             None => Ok(None),
         }
     }
 
+    /// Moves a request that just left the messages queue to the in-flight statistics.
+    fn received(&self, rq: Request) -> Request {
+        rq.with_registration(self.counters.request_in_flight())
+    }
+
+    /// Shuts the server down gracefully, blocking until it is done or `deadline` is reached.
+    ///
+    /// The server stops accepting new connections right away. The requests that have already
+    /// been returned by `recv()` and friends can still be answered, and their responses include
+    /// a `Connection: close` header. Requests that are still waiting to be returned are answered
+    /// with `503 Service Unavailable`, and no other request is read from the clients.
+    ///
+    /// Returns `true` if all the connections have been closed and all the requests answered
+    /// before the deadline.
+    pub fn shutdown(&self, deadline: Instant) -> bool {
+        self.drain.start();
+        self.stop_accepting();
+
+        loop {
+            self.drain.close_idle_connections();
+
+            while let Some(message) = self.messages.try_pop() {
+                match message {
+                    Message::NewRequest(rq) => {
+                        rq.respond(Response::empty(StatusCode(503))).ok();
+                    }
+                    Message::Error(err) => {
+                        // left for `recv()`
+                        self.messages.push(err.into());
+                        break;
+                    }
+                }
+            }
+
+            let stats = self.counters.snapshot();
+            if stats == ServerStats::default() {
+                return true;
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            thread::sleep((deadline - now).min(Duration::from_millis(10)));
+        }
+    }
+
+    /// Makes the accept threads stop.
+    fn stop_accepting(&self) {
+        self.close.store(true, Relaxed);
+        // Connect briefly to ourselves to unblock the accept threads
+        for listening_addr in &self.listening_addrs {
+            let maybe_stream = match listening_addr {
+                ListenAddr::IP(addr) => TcpStream::connect(addr).map(Connection::from),
+                #[cfg(unix)]
+                ListenAddr::Unix(addr) => {
+                    // TODO: use connect_addr when its stabilized.
+                    let path = addr.as_pathname().unwrap();
+                    std::os::unix::net::UnixStream::connect(path).map(Connection::from)
+                }
+            };
+            if let Ok(stream) = maybe_stream {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+
     /// Unblock thread stuck in recv() or incoming_requests().
     /// If there are several such threads, only one is unblocked.
     /// This method allows graceful shutdown of server.
@@ -457,25 +1111,17 @@ impl Iterator for IncomingRequests<'_> {
 
 impl Drop for Server {
     fn drop(&mut self) {
-        self.close.store(true, Relaxed);
-        // Connect briefly to ourselves to unblock the accept thread
-        let maybe_stream = match &self.listening_addr {
-            ListenAddr::IP(addr) => TcpStream::connect(addr).map(Connection::from),
-            #[cfg(unix)]
-            ListenAddr::Unix(addr) => {
-                // TODO: use connect_addr when its stabilized.
-                let path = addr.as_pathname().unwrap();
-                std::os::unix::net::UnixStream::connect(path).map(Connection::from)
-            }
-        };
-        if let Ok(stream) = maybe_stream {
-            let _ = stream.shutdown(Shutdown::Both);
-        }
+        self.stop_accepting();
+
+        // nobody will pop the queue anymore, the connections must not wait for room in it
+        self.messages.unbound();
 
         #[cfg(unix)]
-        if let ListenAddr::Unix(addr) = &self.listening_addr {
-            if let Some(path) = addr.as_pathname() {
-                let _ = std::fs::remove_file(path);
+        for listening_addr in &self.listening_addrs {
+            if let ListenAddr::Unix(addr) = listening_addr {
+                if let Some(path) = addr.as_pathname() {
+                    let _ = std::fs::remove_file(path);
+                }
             }
         }
     }
@@ -1,5 +1,6 @@
 #[cfg(feature = "log")]
-pub(crate) use log::{debug, error};
+#[allow(unused_imports)] // `warn` is only used by the SSL features
+pub(crate) use log::{debug, error, warn};
 
 #[cfg(not(feature = "log"))]
 macro_rules! _debug {
@@ -14,4 +15,11 @@ macro_rules! _error {
 }
 
 #[cfg(not(feature = "log"))]
-pub(crate) use {_debug as debug, _error as error};
+macro_rules! _warn {
+    (target: $target:expr, $($arg:tt)+) => {};
+    ($($arg:tt)+) => {};
+}
+
+#[cfg(not(feature = "log"))]
+#[allow(unused_imports)]
+pub(crate) use {_debug as debug, _error as error, _warn as warn};
@@ -2,15 +2,27 @@ use std::io::Error as IoError;
 use std::io::{self, Cursor, ErrorKind, Read, Write};
 
 use std::fmt;
-use std::net::SocketAddr;
+use std::net::{Shutdown, SocketAddr};
 use std::str::FromStr;
 
-use std::sync::mpsc::Sender;
-
-use crate::util::{EqualReader, FusedReader};
-use crate::{HTTPVersion, Header, Method, Response, StatusCode};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::connection::Connection;
+use crate::drain::Drain;
+use crate::sse::SseSender;
+use crate::stats::Registration;
+use crate::streaming::StreamingResponse;
+#[cfg(feature = "compression")]
+use crate::util::{ContentCoding, DecompressingReader, UnsupportedCoding};
+use crate::util::{EqualReader, FusedReader, LimitedReader};
+use crate::{HTTPVersion, Header, ListenAddr, Method, Response, StatusCode, TlsInfo};
 use chunked_transfer::Decoder;
 
+/// Largest number of codings that `decompress_body()` removes from a body.
+#[cfg(feature = "compression")]
+const MAX_CONTENT_CODINGS: usize = 2;
+
 /// Represents an HTTP request made by a client.
 ///
 /// A `Request` object is what is produced by the server, and is your what
@@ -72,32 +84,31 @@ pub struct Request {
     // true if a `100 Continue` response must be sent when `as_reader()` is called
     must_send_continue: bool,
 
-    // If Some, a message must be sent after responding
-    notify_when_responded: Option<Sender<()>>,
-}
+    // set to true once the body turns out to be larger than its limit, in which case the end
+    // of the body is never read and the connection must be closed
+    body_too_large: Arc<AtomicBool>,
 
-struct NotifyOnDrop<R> {
-    sender: Sender<()>,
-    inner: R,
-}
+    // true if `decompress_body()` was called on a body whose coding isn't supported
+    unsupported_encoding: bool,
 
-impl<R: Read> Read for NotifyOnDrop<R> {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner.read(buf)
-    }
-}
-impl<R: Write> Write for NotifyOnDrop<R> {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.inner.write(buf)
-    }
-    fn flush(&mut self) -> io::Result<()> {
-        self.inner.flush()
-    }
-}
-impl<R> Drop for NotifyOnDrop<R> {
-    fn drop(&mut self) {
-        self.sender.send(()).unwrap();
-    }
+    // keeps this request accounted for in the server's statistics until it is destroyed
+    registration: Option<Registration>,
+
+    // if Some, the response closes the connection once the server is shutting down
+    drain: Option<Arc<Drain>>,
+
+    // address of the listener that accepted the connection, None for test requests
+    listen_addr: Option<ListenAddr>,
+
+    // DER-encoded certificates sent by the client during the TLS handshake, if any
+    peer_certificates: Option<Arc<Vec<Vec<u8>>>>,
+
+    // parameters of the TLS session, None if the connection doesn't use SSL
+    tls_info: Option<Arc<TlsInfo>>,
+
+    // the underlying socket of the connection, to which file responses are sent without going
+    // through the writer if it doesn't use SSL
+    socket: Option<Arc<Connection>>,
 }
 
 /// Error that can happen when building a `Request` object.
@@ -106,6 +117,9 @@ pub enum RequestCreationError {
     /// The client sent an `Expect` header that was not recognized by tiny-http.
     ExpectationFailed,
 
+    /// The client announced a body larger than the maximum allowed size.
+    PayloadTooLarge,
+
     /// Error while reading data from the socket during the creation of the `Request`.
     CreationIoError(IoError),
 }
@@ -125,6 +139,8 @@ impl From<IoError> for RequestCreationError {
 /// It is the responsibility of the `Request` to read only the data of the request and not further.
 ///
 /// The `Write` object will be used by the `Request` to write the response.
+///
+/// If `body_limit` is `Some`, bodies larger than this number of bytes are refused.
 #[allow(clippy::too_many_arguments)]
 pub fn new_request<R, W>(
     secure: bool,
@@ -135,6 +151,7 @@ pub fn new_request<R, W>(
     remote_addr: Option<SocketAddr>,
     mut source_data: R,
     writer: W,
+    body_limit: Option<usize>,
 ) -> Result<Request, RequestCreationError>
 where
     R: Read + Send + 'static,
@@ -171,6 +188,12 @@ where
         }
     };
 
+    if let (Some(content_length), Some(limit)) = (content_length, body_limit) {
+        if content_length > limit {
+            return Err(RequestCreationError::PayloadTooLarge);
+        }
+    }
+
     // true if the client sent a `Connection: upgrade` header
     let connection_upgrade = {
         match headers
@@ -183,6 +206,8 @@ where
         }
     };
 
+    let body_too_large = Arc::new(AtomicBool::new(false));
+
     // we wrap `source_data` around a reading whose nature depends on the transfer-encoding and
     // content-length headers
     let reader = if connection_upgrade {
@@ -218,7 +243,15 @@ where
     } else if transfer_encoding.is_some() {
         // if a transfer-encoding was specified, then "chunked" is ALWAYS applied
         // over the message (RFC2616 #3.6)
-        Box::new(FusedReader::new(Decoder::new(source_data))) as Box<dyn Read + Send + 'static>
+        let reader = FusedReader::new(Decoder::new(source_data));
+        match body_limit {
+            // the size of the body is unknown, so it is checked while it is being read
+            Some(limit) => {
+                let reader = LimitedReader::new(reader, limit).with_notify(body_too_large.clone());
+                Box::new(reader) as Box<dyn Read + Send + 'static>
+            }
+            None => Box::new(reader) as Box<dyn Read + Send + 'static>,
+        }
     } else {
         // if we have neither a Content-Length nor a Transfer-Encoding,
         // assuming that we have no data
@@ -237,7 +270,14 @@ where
         headers,
         body_length: content_length,
         must_send_continue: expects_continue,
-        notify_when_responded: None,
+        body_too_large,
+        unsupported_encoding: false,
+        registration: None,
+        drain: None,
+        listen_addr: None,
+        peer_certificates: None,
+        tls_info: None,
+        socket: None,
     })
 }
 
@@ -293,6 +333,15 @@ impl Request {
         self.remote_addr.as_ref()
     }
 
+    /// Returns the address of the listener that accepted the connection of the client.
+    ///
+    /// This tells requests apart when the server listens on several addresses. Returns `None`
+    /// for requests built with [`TestRequest`](crate::TestRequest).
+    #[inline]
+    pub fn listen_addr(&self) -> Option<&ListenAddr> {
+        self.listen_addr.as_ref()
+    }
+
     /// Sends a response with a `Connection: upgrade` header, then turns the `Request` into a `Stream`.
     ///
     /// The main purpose of this function is to support websockets.
@@ -322,15 +371,89 @@ impl Request {
         self.response_writer.as_mut().unwrap().flush().ok(); // TODO: unused result
 
         let stream = CustomStream::new(self.extract_reader_impl(), self.extract_writer_impl());
-        if let Some(sender) = self.notify_when_responded.take() {
-            let stream = NotifyOnDrop {
-                sender,
-                inner: stream,
-            };
-            Box::new(stream) as Box<dyn ReadWrite + Send>
+        Box::new(stream) as Box<dyn ReadWrite + Send>
+    }
+
+    /// Sets the maximum size in bytes of the body that can be read with `as_reader()`.
+    ///
+    /// This must be called before the first call to `as_reader()`. If the client announced a
+    /// larger body with its `Content-Length` header, the reader fails right away without
+    /// reading anything and no `100 Continue` response is sent. Otherwise the reader fails once
+    /// the body crosses the limit.
+    ///
+    /// Once the body is known to be too large, destroying the request without answering it
+    /// sends a `413 Payload Too Large` response instead of the usual
+    /// `500 Internal Server Error`, and the connection is closed after the response since the
+    /// rest of the body is never read.
+    ///
+    /// A server-wide limit can also be set with [`Limits::max_body_size`](crate::Limits).
+    pub fn set_body_limit(&mut self, limit: usize) {
+        let reader = self.extract_reader_impl();
+
+        let reader = if self.body_length.map_or(false, |len| len > limit) {
+            self.must_send_continue = false;
+            LimitedReader::exceeded(reader)
         } else {
-            Box::new(stream) as Box<dyn ReadWrite + Send>
+            LimitedReader::new(reader, limit)
+        };
+
+        self.data_reader = Some(Box::new(reader.with_notify(self.body_too_large.clone())));
+    }
+
+    /// Decompresses the body read with `as_reader()` according to the `Content-Encoding` header
+    /// of the request.
+    ///
+    /// The `br`, `gzip` and `deflate` codings are supported. Since a small compressed body can
+    /// expand into a huge amount of data, the reader fails once more than `max_size` bytes have
+    /// been decompressed, and destroying the request without answering it then sends a
+    /// `413 Payload Too Large` response and closes the connection, like with
+    /// `set_body_limit()`. The reader also fails if the data is corrupted. Once the body is
+    /// decompressed, `body_length()` returns `None`.
+    ///
+    /// If the body uses another coding, or more than two codings applied on top of each other,
+    /// the reader fails right away without reading anything, no `100 Continue` response is
+    /// sent, and destroying the request without answering it sends a
+    /// `415 Unsupported Media Type` response instead of the usual
+    /// `500 Internal Server Error`.
+    ///
+    /// This must be called before the first call to `as_reader()`. A limit set with
+    /// `set_body_limit()` applies to the compressed body.
+    ///
+    /// Requires the `compression` feature.
+    #[cfg(feature = "compression")]
+    pub fn decompress_body(&mut self, max_size: usize) {
+        let codings = self
+            .headers
+            .iter()
+            .filter(|h| h.field.equiv("Content-Encoding"))
+            .flat_map(|h| h.value.as_str().split(','))
+            .map(|coding| coding.trim())
+            .filter(|coding| !coding.is_empty() && !coding.eq_ignore_ascii_case("identity"))
+            .map(ContentCoding::from_name)
+            .collect::<Option<Vec<_>>>();
+
+        let codings = match codings {
+            Some(codings) if codings.is_empty() => return,
+            // each coding is decompressed up to `max_size`, which must remain reasonable
+            Some(codings) if codings.len() <= MAX_CONTENT_CODINGS => codings,
+            _ => {
+                self.unsupported_encoding = true;
+                self.must_send_continue = false;
+                self.data_reader = Some(Box::new(UnsupportedCoding));
+                return;
+            }
+        };
+
+        // the codings are listed in the order in which they were applied
+        let mut reader = self.extract_reader_impl();
+        for coding in codings.into_iter().rev() {
+            let decompressed = DecompressingReader::new(reader, coding);
+            let limited = LimitedReader::new(decompressed, max_size);
+            reader = Box::new(limited.with_notify(self.body_too_large.clone()));
         }
+
+        self.data_reader = Some(reader);
+        self.body_length = None;
     }
 
     /// Allows to read the body of the request.
@@ -388,16 +511,7 @@ impl Request {
     /// Therefore you should always destroy the `Writer` as soon as possible.
     #[inline]
     pub fn into_writer(mut self) -> Box<dyn Write + Send + 'static> {
-        let writer = self.extract_writer_impl();
-        if let Some(sender) = self.notify_when_responded.take() {
-            let writer = NotifyOnDrop {
-                sender,
-                inner: writer,
-            };
-            Box::new(writer) as Box<dyn Write + Send + 'static>
-        } else {
-            writer
-        }
+        self.extract_writer_impl()
     }
 
     /// Extract the response `Writer` object from the Request, dropping this `Writer` has the same side effects
@@ -433,11 +547,7 @@ impl Request {
     where
         R: Read,
     {
-        let res = self.respond_impl(response);
-        if let Some(sender) = self.notify_when_responded.take() {
-            sender.send(()).unwrap();
-        }
-        res
+        self.respond_impl(response)
     }
 
     fn respond_impl<R>(&mut self, response: Response<R>) -> Result<(), IoError>
@@ -448,17 +558,99 @@ impl Request {
 
         let do_not_send_body = self.method == Method::Head;
 
-        Self::ignore_client_closing_errors(response.raw_print(
+        let response = if self.body_too_large.load(Ordering::Acquire)
+            || self
+                .drain
+                .as_ref()
+                .map_or(false, |drain| drain.is_draining())
+        {
+            response.with_connection_close()
+        } else {
+            response
+        };
+
+        Self::ignore_client_closing_errors(response.raw_print_to_socket(
             writer.by_ref(),
             self.http_version.clone(),
             &self.headers,
             do_not_send_body,
-            None,
+            self.socket.as_deref().filter(|_| !self.secure),
         ))?;
 
         Self::ignore_client_closing_errors(writer.flush())
     }
 
+    /// Turns the request into a stream of server-sent events.
+    ///
+    /// This sends a `200 OK` response with a `text/event-stream` content type right away. Its
+    /// body is made of the events then sent with the returned [`SseSender`], and ends when the
+    /// sender is destroyed.
+    pub fn into_sse(self) -> Result<SseSender, IoError> {
+        let last_event_id = self
+            .headers
+            .iter()
+            .find(|h| h.field.equiv("Last-Event-ID"))
+            .map(|h| h.value.as_str().to_owned());
+
+        let headers = vec![
+            Header::from_bytes(&b"Content-Type"[..], &b"text/event-stream"[..]).unwrap(),
+            Header::from_bytes(&b"Cache-Control"[..], &b"no-cache"[..]).unwrap(),
+        ];
+        let response = self.into_streaming_response(200, headers)?;
+        Ok(SseSender::new(response, last_event_id))
+    }
+
+    /// Sends the status line and the headers of a response, then turns the request into a
+    /// writer of the body of this response.
+    ///
+    /// Contrary to `respond`, the length of the body doesn't need to be known in advance and
+    /// every flush of the returned [`StreamingResponse`] sends the data written so far to the
+    /// client. This suits long-polling and progress reports. The trailers added to the
+    /// `StreamingResponse` are sent once the body ends.
+    ///
+    /// The headers are filtered like with [`Response::with_header`].
+    pub fn into_streaming_response<S>(
+        mut self,
+        status_code: S,
+        headers: Vec<Header>,
+    ) -> Result<StreamingResponse, IoError>
+    where
+        S: Into<StatusCode>,
+    {
+        let mut writer = self.extract_writer_impl();
+
+        let mut response = Response::empty(status_code);
+        for header in headers {
+            response.add_header(header);
+        }
+        if self
+            .drain
+            .as_ref()
+            .map_or(false, |drain| drain.is_draining())
+        {
+            response = response.with_connection_close();
+        }
+
+        let (chunked, closes) = response.raw_print_head(writer.by_ref(), &self.http_version)?;
+        writer.flush()?;
+
+        // the end of the body is marked by closing the connection, so the client can't send
+        // another request on it ; this stops waiting for one
+        if closes {
+            if let Some(socket) = &self.socket {
+                socket.shutdown(Shutdown::Read).ok();
+            }
+        }
+
+        // neither chunked nor delimited by closing if the status code doesn't allow a body
+        let has_body = chunked || closes;
+        if self.method == Method::Head || !has_body {
+            Ok(StreamingResponse::new(Box::new(io::sink()), false))
+        } else {
+            Ok(StreamingResponse::new(writer, chunked))
+        }
+    }
+
     fn ignore_client_closing_errors(result: io::Result<()>) -> io::Result<()> {
         result.or_else(|err| match err.kind() {
             ErrorKind::BrokenPipe => Ok(()),
@@ -469,8 +661,62 @@ impl Request {
         })
     }
 
-    pub(crate) fn with_notify_sender(mut self, sender: Sender<()>) -> Self {
-        self.notify_when_responded = Some(sender);
+    /// Returns the certificate chain sent by the client during the TLS handshake, leaf
+    /// certificate first, each certificate being in the DER format.
+    ///
+    /// The chain has been verified against the authorities of [`ClientAuth`](crate::ClientAuth).
+    /// Returns `None` if the connection doesn't use SSL, or if the client didn't send a
+    /// certificate.
+    #[inline]
+    pub fn peer_certificates(&self) -> Option<&[Vec<u8>]> {
+        self.peer_certificates.as_deref().map(|chain| &chain[..])
+    }
+
+    /// Returns the parameters negotiated during the TLS handshake of the connection, such as the
+    /// protocol version and the cipher suite.
+    ///
+    /// Returns `None` if the connection doesn't use SSL. This is also always `None` with the
+    /// `ssl-native-tls` implementation, which doesn't give access to these parameters.
+    #[inline]
+    pub fn tls_info(&self) -> Option<&TlsInfo> {
+        self.tls_info.as_deref()
+    }
+
+    pub(crate) fn with_registration(mut self, registration: Registration) -> Self {
+        self.registration = Some(registration);
+        self
+    }
+
+    pub(crate) fn with_listen_addr(mut self, listen_addr: ListenAddr) -> Self {
+        self.listen_addr = Some(listen_addr);
+        self
+    }
+
+    pub(crate) fn with_drain(mut self, drain: Arc<Drain>) -> Self {
+        self.drain = Some(drain);
+        self
+    }
+
+    /// Returns a flag that is set to true once the body turns out to be larger than its limit.
+    pub(crate) fn body_too_large_flag(&self) -> Arc<AtomicBool> {
+        self.body_too_large.clone()
+    }
+
+    pub(crate) fn with_peer_certificates(
+        mut self,
+        peer_certificates: Option<Arc<Vec<Vec<u8>>>>,
+    ) -> Self {
+        self.peer_certificates = peer_certificates;
+        self
+    }
+
+    pub(crate) fn with_tls_info(mut self, tls_info: Option<Arc<TlsInfo>>) -> Self {
+        self.tls_info = tls_info;
+        self
+    }
+
+    pub(crate) fn with_socket(mut self, socket: Arc<Connection>) -> Self {
+        self.socket = Some(socket);
         self
     }
 }
@@ -488,11 +734,15 @@ impl fmt::Debug for Request {
 impl Drop for Request {
     fn drop(&mut self) {
         if self.response_writer.is_some() {
-            let response = Response::empty(500);
+            let status_code = if self.body_too_large.load(Ordering::Acquire) {
+                413
+            } else if self.unsupported_encoding {
+                415
+            } else {
+                500
+            };
+            let response = Response::empty(status_code);
             let _ = self.respond_impl(response); // ignoring any potential error
-            if let Some(sender) = self.notify_when_responded.take() {
-                sender.send(()).unwrap();
-            }
         }
     }
 }
@@ -1,6 +1,12 @@
 use crate::common::{HTTPVersion, Header, StatusCode};
+use crate::connection::Connection;
+use crate::util::{is_allowed_trailer, parse_range_header, ChunkedWriter, RangePart, RangesReader};
+#[cfg(feature = "compression")]
+use crate::util::{CompressingReader, ContentCoding};
 use httpdate::HttpDate;
 use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
 use std::sync::mpsc::Receiver;
 
 use std::io::Result as IoResult;
@@ -17,25 +23,29 @@ use std::time::SystemTime;
 /// of one of these will have no effect:
 ///
 ///  - `Connection`
-///  - `Trailer`
 ///  - `Transfer-Encoding`
 ///  - `Upgrade`
 ///
 /// Some headers have special behaviors:
 ///
 ///  - `Content-Encoding`: If you define this header, the library
-///     will assume that the data from the `Read` object has the specified encoding
-///     and will just pass-through.
+///    will assume that the data from the `Read` object has the specified encoding
+///    and will just pass-through. `with_compression` has no effect then.
 ///
 ///  - `Content-Length`: The length of the data should be set manually
-///     using the `Reponse` object's API. Attempting to set the value of this
-///     header will be equivalent to modifying the size of the data but the header
-///     itself may not be present in the final result.
+///    using the `Reponse` object's API. Attempting to set the value of this
+///    header will be equivalent to modifying the size of the data but the header
+///    itself may not be present in the final result.
 ///
 ///  - `Content-Type`: You may only set this header to one value at a time. If you
-///     try to set it more than once, the existing value will be overwritten. This
-///     behavior differs from the default for most headers, which is to allow them to
-///     be set multiple times in the same response.
+///    try to set it more than once, the existing value will be overwritten. This
+///    behavior differs from the default for most headers, which is to allow them to
+///    be set multiple times in the same response.
+///
+///  - `Trailer`: Announces the names of the trailers sent after the body, see
+///    `Response::new`. If it isn't set, it is added with the names of the trailers already
+///    available when the response is sent. It is removed from the response whenever the
+///    trailers can't be sent.
 ///
 pub struct Response<R> {
     reader: R,
@@ -43,6 +53,11 @@ pub struct Response<R> {
     headers: Vec<Header>,
     data_length: Option<usize>,
     chunked_threshold: Option<usize>,
+    additional_headers: Option<Receiver<Header>>,
+    compression: bool,
+    // shares its position with the reader when it is a file, which allows answering range
+    // requests by seeking
+    file: Option<File>,
 }
 
 /// A `Response` without a template parameter.
@@ -109,6 +124,92 @@ where
     Ok(())
 }
 
+/// Returns true if the client sent a `TE: trailers` header.
+fn accepts_trailers(request_headers: &[Header]) -> bool {
+    request_headers
+        .iter()
+        .filter(|h| h.field.equiv("TE"))
+        .flat_map(|h| crate::util::parse_header_value(h.value.as_str()))
+        .any(|(value, _)| value.trim().eq_ignore_ascii_case("trailers"))
+}
+
+/// Bodies smaller than this are not worth compressing.
+#[cfg(feature = "compression")]
+const MIN_COMPRESSED_LENGTH: usize = 1024;
+
+/// Returns true for the media types whose data is usually compressed already.
+#[cfg(feature = "compression")]
+fn is_compressed_type(content_type: &str) -> bool {
+    let media_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+
+    if media_type == "image/svg+xml" {
+        return false;
+    }
+
+    media_type.starts_with("image/")
+        || media_type.starts_with("audio/")
+        || media_type.starts_with("video/")
+        || matches!(
+            media_type.as_str(),
+            "application/gzip"
+                | "application/vnd.rar"
+                | "application/x-7z-compressed"
+                | "application/x-bzip2"
+                | "application/x-gzip"
+                | "application/x-rar-compressed"
+                | "application/x-xz"
+                | "application/zip"
+                | "application/zstd"
+                | "font/woff"
+                | "font/woff2"
+        )
+}
+
+/// Picks the content coding preferred by the client among the supported ones, according to
+/// the `Accept-Encoding` header of the request.
+#[cfg(feature = "compression")]
+fn choose_content_coding(request_headers: &[Header]) -> Option<ContentCoding> {
+    // by order of preference when the client accepts several of them equally
+    const SUPPORTED: [ContentCoding; 3] = [
+        ContentCoding::Brotli,
+        ContentCoding::Gzip,
+        ContentCoding::Deflate,
+    ];
+
+    let mut qualities = [None; 3];
+    let mut any = None;
+
+    let accepted = request_headers
+        .iter()
+        .filter(|h| h.field.equiv("Accept-Encoding"))
+        .flat_map(|h| crate::util::parse_header_value(h.value.as_str()));
+    for (coding, quality) in accepted {
+        if coding == "*" {
+            any = Some(quality);
+        } else if let Some(coding) = ContentCoding::from_name(coding) {
+            let index = SUPPORTED.iter().position(|&c| c == coding).unwrap();
+            qualities[index] = Some(quality);
+        }
+    }
+
+    // `*` applies to the codings not explicitly listed, and q=0 means "not acceptable"
+    let mut chosen = None;
+    let mut best = 0.0;
+    for (&coding, quality) in SUPPORTED.iter().zip(qualities.iter()) {
+        let quality = quality.or(any).unwrap_or(0.0);
+        if quality > best {
+            chosen = Some(coding);
+            best = quality;
+        }
+    }
+    chosen
+}
+
 fn choose_transfer_encoding(
     status_code: StatusCode,
     request_headers: &[Header],
@@ -189,8 +290,17 @@ where
 {
     /// Creates a new Response object.
     ///
-    /// The `additional_headers` argument is a receiver that
-    ///  may provide headers even after the response has been sent.
+    /// The `additional_headers` argument is a receiver that may provide headers after the body
+    /// has been sent, for instance a checksum of the data. The headers available once the end
+    /// of `data` has been reached are sent as trailers, provided that the client announced
+    /// their support with a `TE: trailers` header. The response then always uses the chunked
+    /// transfer encoding, and their names can be announced with a `Trailer` header, which
+    /// otherwise lists the trailers already available when the response is sent. Otherwise
+    /// the `Trailer` header is removed, the headers already available when the response is
+    /// sent are added to the other ones, and the later ones are dropped.
+    ///
+    /// The fields that can't be sent after the body, such as `Content-Length`, `Content-Type`
+    /// or `Set-Cookie`, are dropped from the trailers (see RFC 9110 section 6.5.1).
     ///
     /// All the other arguments are straight-forward.
     pub fn new(
@@ -206,19 +316,15 @@ where
             headers: Vec::with_capacity(16),
             data_length,
             chunked_threshold: None,
+            additional_headers,
+            compression: false,
+            file: None,
         };
 
         for h in headers {
             response.add_header(h)
         }
 
-        // dummy implementation
-        if let Some(additional_headers) = additional_headers {
-            for h in additional_headers.iter() {
-                response.add_header(h)
-            }
-        }
-
         response
     }
 
@@ -231,6 +337,21 @@ where
         self
     }
 
+    /// Compresses the body if the client supports it, with brotli, gzip or deflate depending on
+    /// the `Accept-Encoding` header of the request.
+    ///
+    /// A compressed body is sent without a `Content-Length`, and a `Vary: Accept-Encoding`
+    /// header is added since the response depends on the request headers. Bodies smaller than
+    /// 1 kiB and content types that are usually compressed already, such as images, videos or
+    /// archives, are sent as is.
+    ///
+    /// Requires the `compression` feature.
+    #[cfg(feature = "compression")]
+    pub fn with_compression(mut self) -> Response<R> {
+        self.compression = true;
+        self
+    }
+
     /// Convert the response into the underlying `Read` type.
     ///
     /// This is mainly useful for testing as it must consume the `Response`.
@@ -256,7 +377,6 @@ where
 
         // ignoring forbidden headers
         if header.field.equiv("Connection")
-            || header.field.equiv("Trailer")
             || header.field.equiv("Transfer-Encoding")
             || header.field.equiv("Upgrade")
         {
@@ -298,6 +418,13 @@ where
         self
     }
 
+    /// Adds a `Connection: close` header, which can't be added with `with_header`.
+    pub(crate) fn with_connection_close(mut self) -> Response<R> {
+        self.headers
+            .push(Header::from_bytes(&b"Connection"[..], &b"close"[..]).unwrap());
+        self
+    }
+
     /// Returns the same request, but with a different status code.
     #[inline]
     pub fn with_status_code<S>(mut self, code: S) -> Response<R>
@@ -319,6 +446,9 @@ where
             status_code: self.status_code,
             data_length,
             chunked_threshold: self.chunked_threshold,
+            additional_headers: self.additional_headers,
+            compression: self.compression,
+            file: None,
         }
     }
 
@@ -332,22 +462,148 @@ where
     ///
     /// Note: does not flush the writer.
     pub fn raw_print<W: Write>(
+        self,
+        writer: W,
+        http_version: HTTPVersion,
+        request_headers: &[Header],
+        do_not_send_body: bool,
+        upgrade: Option<&str>,
+    ) -> IoResult<()> {
+        self.print(
+            writer,
+            http_version,
+            request_headers,
+            do_not_send_body,
+            upgrade,
+            None,
+        )
+    }
+
+    /// Same as `raw_print`, except that a whole file is sent straight to `socket`, which must
+    /// be the one `writer` writes to without any encryption, instead of being copied through
+    /// the writer.
+    pub(crate) fn raw_print_to_socket<W: Write>(
+        self,
+        writer: W,
+        http_version: HTTPVersion,
+        request_headers: &[Header],
+        do_not_send_body: bool,
+        socket: Option<&Connection>,
+    ) -> IoResult<()> {
+        self.print(
+            writer,
+            http_version,
+            request_headers,
+            do_not_send_body,
+            None,
+            socket,
+        )
+    }
+
+    fn print<W: Write>(
         mut self,
         mut writer: W,
         http_version: HTTPVersion,
         request_headers: &[Header],
         do_not_send_body: bool,
         upgrade: Option<&str>,
+        socket: Option<&Connection>,
     ) -> IoResult<()> {
+        // checking whether to ignore the body of the response
+        let do_not_send_body = do_not_send_body
+            || match self.status_code.0 {
+                // status code 1xx, 204 and 304 MUST not include a body
+                100..=199 | 204 | 304 => true,
+                _ => false,
+            };
+
+        // answering range requests when the body is a file
+        let mut file = self.file.take();
+        let ranges = match file {
+            Some(_) if upgrade.is_none() && !do_not_send_body => {
+                self.select_ranges(request_headers)
+            }
+            _ => None,
+        };
+
+        // compressing the body if the client supports it
+        #[cfg(feature = "compression")]
+        let coding = if upgrade.is_none() && ranges.is_none() {
+            self.negotiate_compression(request_headers)
+        } else {
+            None
+        };
+        #[cfg(not(feature = "compression"))]
+        let coding: Option<()> = None;
+
+        // the whole file can be sent directly to the socket, unless the data must be modified
+        let direct = match socket {
+            Some(socket)
+                if upgrade.is_none()
+                    && !do_not_send_body
+                    && ranges.is_none()
+                    && coding.is_none() =>
+            {
+                file.take().map(|file| (file, socket))
+            }
+            _ => None,
+        };
+
+        // the additional headers can only be sent as trailers if the client accepts them
+        let mut additional_headers = self.additional_headers.take();
+        let may_send_trailers = additional_headers.is_some()
+            && !do_not_send_body
+            && upgrade.is_none()
+            && http_version > (1, 0)
+            && accepts_trailers(request_headers);
+
         let mut transfer_encoding = Some(choose_transfer_encoding(
             self.status_code,
             request_headers,
             &http_version,
             &self.data_length,
-            false, /* TODO */
-            self.chunked_threshold(),
+            may_send_trailers,
+            match (&direct, self.chunked_threshold) {
+                // splitting the file into chunks would require copying it
+                (Some(_), None) => usize::MAX,
+                _ => self.chunked_threshold(),
+            },
         ));
 
+        let trailers = match transfer_encoding {
+            Some(TransferEncoding::Chunked) if may_send_trailers => additional_headers.take(),
+            _ => None,
+        };
+        if trailers.is_none() {
+            // sending the headers available so far along with the other ones instead
+            self.headers.retain(|h| !h.field.equiv("Trailer"));
+            if let Some(additional_headers) = additional_headers {
+                for h in additional_headers.try_iter() {
+                    self.add_header(h);
+                }
+            }
+        }
+
+        // announcing the trailers that are already known, unless the caller did it
+        let mut early_trailers = Vec::new();
+        if let Some(trailers) = &trailers {
+            early_trailers.extend(trailers.try_iter());
+            if !self.headers.iter().any(|h| h.field.equiv("Trailer")) {
+                let mut names: Vec<&str> = Vec::new();
+                for trailer in early_trailers.iter().filter(|t| is_allowed_trailer(t)) {
+                    let name = trailer.field.as_str().as_str();
+                    if !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+                        names.push(name);
+                    }
+                }
+                if !names.is_empty() {
+                    self.headers.push(
+                        Header::from_bytes(&b"Trailer"[..], names.join(", ").as_bytes()).unwrap(),
+                    );
+                }
+            }
+        }
+
         // add `Date` if not in the headers
         if !self.headers.iter().any(|h| h.field.equiv("Date")) {
             self.headers.insert(0, build_date_header());
@@ -377,24 +633,22 @@ where
         // if the transfer encoding is identity, the content length must be known ; therefore if
         // we don't know it, we buffer the entire response first here
         // while this is an expensive operation, it is only ever needed for clients using HTTP 1.0
+        let mut reader: Box<dyn Read> = match (ranges, coding) {
+            (Some(parts), _) => Box::new(RangesReader::new(self.reader, file.unwrap(), parts)),
+            #[cfg(feature = "compression")]
+            (None, Some(coding)) => Box::new(CompressingReader::new(self.reader, coding)),
+            (None, _) => Box::new(self.reader),
+        };
         let (mut reader, data_length): (Box<dyn Read>, _) =
             match (self.data_length, transfer_encoding) {
-                (Some(l), _) => (Box::new(self.reader), Some(l)),
+                (Some(l), _) => (reader, Some(l)),
                 (None, Some(TransferEncoding::Identity)) => {
                     let mut buf = Vec::new();
-                    self.reader.read_to_end(&mut buf)?;
+                    reader.read_to_end(&mut buf)?;
                     let l = buf.len();
                     (Box::new(Cursor::new(buf)), Some(l))
                 }
-                _ => (Box::new(self.reader), None),
-            };
-
-        // checking whether to ignore the body of the response
-        let do_not_send_body = do_not_send_body
-            || match self.status_code.0 {
-                // status code 1xx, 204 and 304 MUST not include a body
-                100..=199 | 204 | 304 => true,
-                _ => false,
+                _ => (reader, None),
             };
 
         // preparing headers for transfer
@@ -430,19 +684,36 @@ where
         // sending the body
         if !do_not_send_body {
             match transfer_encoding {
-                Some(TransferEncoding::Chunked) => {
-                    use chunked_transfer::Encoder;
+                Some(TransferEncoding::Chunked) => match trailers {
+                    Some(trailers) => {
+                        let mut writer = ChunkedWriter::new(writer);
+                        io::copy(&mut reader, &mut writer)?;
+                        early_trailers.extend(trailers.try_iter());
+                        writer.finish(&early_trailers)?;
+                    }
+                    None => {
+                        use chunked_transfer::Encoder;
 
-                    let mut writer = Encoder::new(writer);
-                    io::copy(&mut reader, &mut writer)?;
-                }
+                        let mut writer = Encoder::new(writer);
+                        io::copy(&mut reader, &mut writer)?;
+                    }
+                },
 
                 Some(TransferEncoding::Identity) => {
                     assert!(data_length.is_some());
                     let data_length = data_length.unwrap();
 
                     if data_length >= 1 {
-                        io::copy(&mut reader, &mut writer)?;
+                        match direct {
+                            Some((file, socket)) => {
+                                // the headers must reach the socket first
+                                writer.flush()?;
+                                socket.send_file(&file, data_length as u64)?;
+                            }
+                            None => {
+                                io::copy(&mut reader, &mut writer)?;
+                            }
+                        }
                     }
                 }
 
@@ -453,6 +724,222 @@ where
         Ok(())
     }
 
+    /// Handles the `Range` header of the request, for a body that can be seeked. Returns the
+    /// parts of the body to send, after updating the status code and the headers, or `None`
+    /// to send the whole body.
+    fn select_ranges(&mut self, request_headers: &[Header]) -> Option<Vec<RangePart>> {
+        if self.status_code != 200 {
+            return None;
+        }
+        let length = self.data_length? as u64;
+
+        if !self.headers.iter().any(|h| h.field.equiv("Accept-Ranges")) {
+            self.headers
+                .push(Header::from_bytes(&b"Accept-Ranges"[..], &b"bytes"[..]).unwrap());
+        }
+
+        let request_header = |name: &'static str| {
+            request_headers
+                .iter()
+                .find(|h| h.field.equiv(name))
+                .map(|h| h.value.as_str())
+        };
+        let range = request_header("Range")?;
+        if let Some(validator) = request_header("If-Range") {
+            if !self.is_current(validator) {
+                return None;
+            }
+        }
+        let ranges = parse_range_header(range, length)?;
+
+        let content_range = |first: u64, last: u64| {
+            let value = format!("bytes {}-{}/{}", first, last, length);
+            Header::from_bytes(&b"Content-Range"[..], value.as_bytes()).unwrap()
+        };
+
+        let parts = match *ranges.as_slice() {
+            [] => {
+                self.status_code = StatusCode(416);
+                let value = format!("bytes */{}", length);
+                self.headers
+                    .push(Header::from_bytes(&b"Content-Range"[..], value.as_bytes()).unwrap());
+                self.data_length = Some(0);
+                Vec::new()
+            }
+
+            [(first, last)] => {
+                self.status_code = StatusCode(206);
+                self.headers.push(content_range(first, last));
+                self.data_length = Some((last - first + 1) as usize);
+                vec![RangePart {
+                    prefix: Vec::new(),
+                    start: first,
+                    length: last - first + 1,
+                }]
+            }
+
+            _ => {
+                let mut hasher = RandomState::new().build_hasher();
+                hasher.write_u64(length);
+                let boundary = format!("{:016x}", hasher.finish());
+
+                let content_type = self
+                    .headers
+                    .iter()
+                    .find(|h| h.field.equiv("Content-Type"))
+                    .map(|h| h.value.clone());
+
+                let mut parts = Vec::with_capacity(ranges.len() + 1);
+                for (first, last) in ranges {
+                    let mut prefix = format!("\r\n--{}\r\n", boundary);
+                    if let Some(ref content_type) = content_type {
+                        prefix.push_str(&format!("Content-Type: {}\r\n", content_type));
+                    }
+                    prefix.push_str(&format!("{}\r\n\r\n", content_range(first, last)));
+                    parts.push(RangePart {
+                        prefix: prefix.into_bytes(),
+                        start: first,
+                        length: last - first + 1,
+                    });
+                }
+                parts.push(RangePart {
+                    prefix: format!("\r\n--{}--\r\n", boundary).into_bytes(),
+                    start: 0,
+                    length: 0,
+                });
+
+                self.status_code = StatusCode(206);
+                let content_type = format!("multipart/byteranges; boundary={}", boundary);
+                self.add_header(
+                    Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes()).unwrap(),
+                );
+                let total: u64 = parts.iter().map(|p| p.prefix.len() as u64 + p.length).sum();
+                self.data_length = Some(total as usize);
+                parts
+            }
+        };
+
+        Some(parts)
+    }
+
+    /// Returns true if the validator of an `If-Range` header matches the `ETag` or the
+    /// `Last-Modified` header of this response.
+    fn is_current(&self, validator: &str) -> bool {
+        let validator = validator.trim();
+        let header = |name: &'static str| {
+            self.headers
+                .iter()
+                .find(|h| h.field.equiv(name))
+                .map(|h| h.value.as_str().trim())
+        };
+
+        // entity tags use the strong comparison, so weak ones never match
+        if validator.starts_with('"') {
+            return header("ETag") == Some(validator);
+        }
+        if validator.starts_with("W/") {
+            return false;
+        }
+
+        match header("Last-Modified").map(HttpDate::from_str) {
+            Some(Ok(modified)) => HttpDate::from_str(validator).ok() == Some(modified),
+            _ => false,
+        }
+    }
+
+    /// Returns true if `with_compression` may compress the body of this response.
+    #[cfg(feature = "compression")]
+    fn is_compressible(&self) -> bool {
+        let has_body = !matches!(self.status_code.0, 100..=199 | 204 | 304);
+        let is_small = self
+            .data_length
+            .map_or(false, |len| len < MIN_COMPRESSED_LENGTH);
+        let is_encoded = self
+            .headers
+            .iter()
+            .any(|h| h.field.equiv("Content-Encoding"));
+        let is_compressed = self
+            .headers
+            .iter()
+            .any(|h| h.field.equiv("Content-Type") && is_compressed_type(h.value.as_str()));
+
+        has_body && !is_small && !is_encoded && !is_compressed
+    }
+
+    /// Picks the coding of the body if `with_compression` was called and the client supports
+    /// one, and adds the headers that go with it.
+    #[cfg(feature = "compression")]
+    fn negotiate_compression(&mut self, request_headers: &[Header]) -> Option<ContentCoding> {
+        if !self.compression || !self.is_compressible() {
+            return None;
+        }
+
+        // the response depends on the request headers, even if it isn't compressed
+        if !self.headers.iter().any(|h| {
+            h.field.equiv("Vary")
+                && h.value
+                    .as_str()
+                    .split(',')
+                    .any(|v| v.trim().eq_ignore_ascii_case("Accept-Encoding"))
+        }) {
+            self.headers
+                .push(Header::from_bytes(&b"Vary"[..], &b"Accept-Encoding"[..]).unwrap());
+        }
+
+        let coding = choose_content_coding(request_headers)?;
+        self.headers
+            .push(Header::from_bytes(&b"Content-Encoding"[..], coding.name().as_bytes()).unwrap());
+        // the length of the compressed data isn't known in advance
+        self.data_length = None;
+        Some(coding)
+    }
+
+    /// Sends the status line and the headers of a response whose body is written afterwards,
+    /// piece by piece, and whose length isn't known in advance.
+    ///
+    /// Returns whether the body must use the chunked transfer encoding, and whether the end of
+    /// the body must be marked by closing the connection, which is the case when the client
+    /// only speaks HTTP 1.0. Neither is true if the status code doesn't allow a body.
+    ///
+    /// Note: does not flush the writer.
+    pub(crate) fn raw_print_head<W: Write>(
+        mut self,
+        writer: W,
+        http_version: &HTTPVersion,
+    ) -> IoResult<(bool, bool)> {
+        if !self.headers.iter().any(|h| h.field.equiv("Date")) {
+            self.headers.insert(0, build_date_header());
+        }
+        if !self.headers.iter().any(|h| h.field.equiv("Server")) {
+            self.headers.insert(
+                0,
+                Header::from_bytes(&b"Server"[..], &b"tiny-http (Rust)"[..]).unwrap(),
+            );
+        }
+
+        // status code 1xx, 204 and 304 MUST not include a body
+        let has_body = match self.status_code.0 {
+            100..=199 | 204 | 304 => false,
+            _ => true,
+        };
+
+        let chunked = has_body && *http_version > (1, 0);
+        let closes = has_body && !chunked;
+        if chunked {
+            self.headers
+                .push(Header::from_bytes(&b"Transfer-Encoding"[..], &b"chunked"[..]).unwrap());
+        } else {
+            // no trailers can be sent
+            self.headers.retain(|h| !h.field.equiv("Trailer"));
+            if closes && !self.headers.iter().any(|h| h.field.equiv("Connection")) {
+                self = self.with_connection_close();
+            }
+        }
+
+        write_message_header(writer, http_version, &self.status_code, &self.headers)?;
+        Ok((chunked, closes))
+    }
+
     /// Retrieves the current value of the `Response` status code
     pub fn status_code(&self) -> StatusCode {
         self.status_code
@@ -481,6 +968,9 @@ where
             headers: self.headers,
             data_length: self.data_length,
             chunked_threshold: self.chunked_threshold,
+            additional_headers: self.additional_headers,
+            compression: self.compression,
+            file: self.file,
         }
     }
 }
@@ -490,16 +980,30 @@ impl Response<File> {
     ///
     /// The `Content-Type` will **not** be automatically detected,
     ///  you must set it yourself.
+    ///
+    /// Range requests are supported: a request with a `Range` header gets a
+    /// `206 Partial Content` response with the requested part of the file, or several ones
+    /// in a `multipart/byteranges` body, and a `416 Range Not Satisfiable` response if none of
+    /// the ranges are within the file. An `If-Range` header is compared to the `ETag` or
+    /// `Last-Modified` header of the response, if any, to send the whole file when it changed.
+    ///
+    /// When the connection doesn't use SSL and the file is sent whole and uncompressed, it is
+    /// written directly to the socket with a `Content-Length`, unless `with_chunked_threshold`
+    /// was called. On Linux, this is done with `sendfile`, without copying the data through
+    /// userspace.
     pub fn from_file(file: File) -> Response<File> {
         let file_size = file.metadata().ok().map(|v| v.len() as usize);
+        let seekable = file.try_clone().ok();
 
-        Response::new(
+        let mut response = Response::new(
             StatusCode(200),
             Vec::with_capacity(0),
             file,
             file_size,
             None,
-        )
+        );
+        response.file = seekable;
+        response
     }
 }
 
@@ -569,6 +1073,10 @@ impl Clone for Response<io::Empty> {
             headers: self.headers.clone(),
             data_length: self.data_length,
             chunked_threshold: self.chunked_threshold,
+            // a receiver can't be shared
+            additional_headers: None,
+            compression: self.compression,
+            file: None,
         }
     }
 }
@@ -0,0 +1,215 @@
+use crate::StreamingResponse;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult, Write};
+use std::time::Duration;
+
+/// An event to send with an [`SseSender`].
+///
+/// ```
+/// use std::time::Duration;
+/// use tiny_http::SseEvent;
+///
+/// let event = SseEvent::new("{\"progress\": 50}")
+///     .with_name("progress")
+///     .with_id("42")
+///     .with_retry(Duration::from_secs(5));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    name: Option<String>,
+    id: Option<String>,
+    data: String,
+    retry: Option<Duration>,
+}
+
+impl SseEvent {
+    /// Builds an event carrying `data`, which may span several lines.
+    pub fn new<S>(data: S) -> SseEvent
+    where
+        S: Into<String>,
+    {
+        SseEvent {
+            name: None,
+            id: None,
+            data: data.into(),
+            retry: None,
+        }
+    }
+
+    /// Sets the type of the event, sent in the `event` field. The browsers dispatch the events
+    /// without a type as `message` events.
+    pub fn with_name<S>(mut self, name: S) -> SseEvent
+    where
+        S: Into<String>,
+    {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the identifier of the event, which the client sends back in the `Last-Event-ID`
+    /// header when it reconnects.
+    pub fn with_id<S>(mut self, id: S) -> SseEvent
+    where
+        S: Into<String>,
+    {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Sets the delay after which the client reconnects if the connection is lost.
+    pub fn with_retry(mut self, retry: Duration) -> SseEvent {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Encodes the event in the `text/event-stream` format.
+    fn encode(&self) -> IoResult<Vec<u8>> {
+        let mut encoded = Vec::with_capacity(self.data.len() + 32);
+
+        if let Some(ref name) = self.name {
+            check_single_line(name)?;
+            writeln!(encoded, "event: {}", name)?;
+        }
+        if let Some(ref id) = self.id {
+            check_single_line(id)?;
+            if id.contains('\0') {
+                return Err(IoError::new(
+                    ErrorKind::InvalidInput,
+                    "Event IDs can't contain NUL characters",
+                ));
+            }
+            writeln!(encoded, "id: {}", id)?;
+        }
+        if let Some(retry) = self.retry {
+            writeln!(encoded, "retry: {}", retry.as_millis())?;
+        }
+
+        // the client splits lines on CRLF, CR and LF alike
+        let data = self.data.replace("\r\n", "\n").replace('\r', "\n");
+        for line in data.split('\n') {
+            writeln!(encoded, "data: {}", line)?;
+        }
+
+        encoded.push(b'\n');
+        Ok(encoded)
+    }
+}
+
+fn check_single_line(field: &str) -> IoResult<()> {
+    if field.contains(|c| c == '\r' || c == '\n') {
+        return Err(IoError::new(
+            ErrorKind::InvalidInput,
+            "Event names and IDs can't span several lines",
+        ));
+    }
+    Ok(())
+}
+
+/// Sends [server-sent events](https://html.spec.whatwg.org/multipage/server-sent-events.html)
+/// to a client. Obtained with [`Request::into_sse`](crate::Request::into_sse).
+///
+/// Each event is sent to the client right away. The stream ends when the sender is
+/// destroyed. Sending fails once the client has disconnected.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// # let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+/// let request = server.recv().unwrap();
+/// let mut sender = request.into_sse().unwrap();
+///
+/// let mut count = sender.last_event_id().and_then(|id| id.parse().ok()).unwrap_or(0);
+/// loop {
+///     count += 1;
+///     let event = tiny_http::SseEvent::new("tick").with_id(count.to_string());
+///     if sender.send(&event).is_err() {
+///         break;
+///     }
+///     std::thread::sleep(Duration::from_secs(1));
+/// }
+/// ```
+pub struct SseSender {
+    response: StreamingResponse,
+    last_event_id: Option<String>,
+}
+
+impl SseSender {
+    pub(crate) fn new(response: StreamingResponse, last_event_id: Option<String>) -> SseSender {
+        SseSender {
+            response,
+            last_event_id,
+        }
+    }
+
+    /// Returns the value of the `Last-Event-ID` header of the request, which is the ID of the
+    /// last event received by a client reconnecting after losing the connection.
+    #[inline]
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_event_id.as_deref()
+    }
+
+    /// Sends an event.
+    ///
+    /// Fails with an `InvalidInput` error if the name or the ID of the event spans several
+    /// lines.
+    pub fn send(&mut self, event: &SseEvent) -> IoResult<()> {
+        let encoded = event.encode()?;
+        self.write(&encoded)
+    }
+
+    /// Sends an event made of `data` only.
+    pub fn send_data(&mut self, data: &str) -> IoResult<()> {
+        self.send(&SseEvent::new(data))
+    }
+
+    /// Sends a comment, which the client ignores.
+    ///
+    /// Sending a comment every few seconds keeps proxies from closing the connection while no
+    /// event is sent, and detects the clients that are gone. See also `keep_alive()`.
+    pub fn send_comment(&mut self, comment: &str) -> IoResult<()> {
+        let mut encoded = Vec::with_capacity(comment.len() + 4);
+        let comment = comment.replace("\r\n", "\n").replace('\r', "\n");
+        for line in comment.split('\n') {
+            writeln!(encoded, ":{}", line)?;
+        }
+        encoded.push(b'\n');
+        self.write(&encoded)
+    }
+
+    /// Sends an empty comment, to keep the connection alive.
+    pub fn keep_alive(&mut self) -> IoResult<()> {
+        self.write(b":\n\n")
+    }
+
+    fn write(&mut self, data: &[u8]) -> IoResult<()> {
+        self.response.write_all(data)?;
+        self.response.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SseEvent;
+    use std::time::Duration;
+
+    #[test]
+    fn test_encode() {
+        let event = SseEvent::new("first\r\nsecond\rthird\n")
+            .with_name("update")
+            .with_id("7")
+            .with_retry(Duration::from_secs(3));
+        assert_eq!(
+            event.encode().unwrap(),
+            b"event: update\nid: 7\nretry: 3000\n\
+              data: first\ndata: second\ndata: third\ndata: \n\n"
+        );
+
+        assert_eq!(SseEvent::new("").encode().unwrap(), b"data: \n\n");
+    }
+
+    #[test]
+    fn test_encode_invalid() {
+        assert!(SseEvent::new("").with_name("a\nb").encode().is_err());
+        assert!(SseEvent::new("").with_id("a\rb").encode().is_err());
+        assert!(SseEvent::new("").with_id("a\0b").encode().is_err());
+    }
+}
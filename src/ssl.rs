@@ -5,12 +5,19 @@
 //! trait contract and specific implementations are re-exported as [`SslContextImpl`] and [`SslStream`].
 //! The concrete type of these aliases will depend on which module you enable in `Cargo.toml`.
 
+#[cfg(any(
+    feature = "ssl-openssl",
+    feature = "ssl-rustls",
+    feature = "ssl-native-tls"
+))]
+mod split;
+
 #[cfg(feature = "ssl-openssl")]
 pub(crate) mod openssl;
 #[cfg(feature = "ssl-openssl")]
 pub(crate) use self::openssl::OpenSslContext as SslContextImpl;
 #[cfg(feature = "ssl-openssl")]
-pub(crate) use self::openssl::SplitOpenSslStream as SslStream;
+pub(crate) use self::openssl::OpenSslStream as SslStream;
 
 #[cfg(feature = "ssl-rustls")]
 pub(crate) mod rustls;
@@ -25,3 +32,38 @@ pub(crate) mod native_tls;
 pub(crate) use self::native_tls::NativeTlsContext as SslContextImpl;
 #[cfg(feature = "ssl-native-tls")]
 pub(crate) use self::native_tls::NativeTlsStream as SslStream;
+
+/// Returns true if a certificate or a key looks like PEM rather than DER.
+#[cfg(any(feature = "ssl-openssl", feature = "ssl-rustls"))]
+pub(crate) fn is_pem(data: &[u8]) -> bool {
+    data.windows(11).any(|window| window == b"-----BEGIN ")
+}
+
+/// Version of the TLS protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TlsVersion {
+    /// TLS 1.0.
+    Tls1_0,
+    /// TLS 1.1.
+    Tls1_1,
+    /// TLS 1.2.
+    Tls1_2,
+    /// TLS 1.3.
+    Tls1_3,
+}
+
+/// Parameters of the TLS session of a connection, as negotiated during the handshake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+    /// Version of the protocol.
+    pub version: TlsVersion,
+    /// Name of the cipher suite, as reported by the SSL implementation.
+    ///
+    /// For instance `TLS_AES_256_GCM_SHA384` with OpenSSL and `TLS13_AES_256_GCM_SHA384` with
+    /// Rustls.
+    pub cipher_suite: String,
+    /// Host name requested by the client through SNI, if any.
+    pub server_name: Option<String>,
+    /// Protocol negotiated through ALPN, if any. See `SslConfig::alpn_protocols`.
+    pub alpn_protocol: Option<Vec<u8>>,
+}
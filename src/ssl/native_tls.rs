@@ -1,79 +1,91 @@
 use crate::connection::Connection;
+use crate::ssl::split::{MemoryTransport, SplitTlsStream, TlsSession};
 use crate::util::refined_tcp_stream::Stream as RefinedStream;
+use crate::{SslConfig, TlsInfo, TlsVersion};
 use std::error::Error;
-use std::io::{Read, Write};
-use std::net::{Shutdown, SocketAddr};
-use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use zeroize::Zeroizing;
 
-/// A wrapper around a `native_tls` stream.
-///
-/// Uses an internal Mutex to permit disparate reader & writer threads to access the stream independently.
-#[derive(Clone)]
-pub(crate) struct NativeTlsStream(Arc<Mutex<native_tls::TlsStream<Connection>>>);
+/// A `native_tls` stream which can be read from and written to concurrently.
+pub(crate) type NativeTlsStream = SplitTlsStream<NativeTlsSession>;
 
-// These struct methods form the implict contract for swappable TLS implementations
-impl NativeTlsStream {
-    pub(crate) fn peer_addr(&mut self) -> std::io::Result<Option<SocketAddr>> {
-        self.0
-            .lock()
-            .expect("Failed to lock SSL stream mutex")
-            .get_mut()
-            .peer_addr()
-    }
-
-    pub(crate) fn shutdown(&mut self, how: Shutdown) -> std::io::Result<()> {
-        self.0
-            .lock()
-            .expect("Failed to lock SSL stream mutex")
-            .get_mut()
-            .shutdown(how)
-    }
-}
+pub(crate) type NativeTlsSession = native_tls::TlsStream<MemoryTransport>;
 
-impl Read for NativeTlsStream {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.0
-            .lock()
-            .expect("Failed to lock SSL stream mutex")
-            .read(buf)
+impl TlsSession for NativeTlsSession {
+    fn transport(&mut self) -> &mut MemoryTransport {
+        self.get_mut()
     }
 }
 
-impl Write for NativeTlsStream {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.0
-            .lock()
-            .expect("Failed to lock SSL stream mutex")
-            .write(buf)
+impl NativeTlsStream {
+    pub(crate) fn peer_certificates(&self) -> Option<Vec<Vec<u8>>> {
+        let certificate = self.session().peer_certificate().ok()??;
+        Some(vec![certificate.to_der().ok()?])
     }
 
-    fn flush(&mut self) -> std::io::Result<()> {
-        self.0
-            .lock()
-            .expect("Failed to lock SSL stream mutex")
-            .flush()
+    pub(crate) fn tls_info(&self) -> Option<TlsInfo> {
+        None
     }
 }
 
 pub(crate) struct NativeTlsContext(native_tls::TlsAcceptor);
 
 impl NativeTlsContext {
-    pub fn from_pem(
-        certificates: Vec<u8>,
-        private_key: Zeroizing<Vec<u8>>,
-    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+    pub fn from_config(config: SslConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        if !config.server_names.is_empty() {
+            return Err(
+                "Choosing the certificate by server name isn't supported with native-tls".into(),
+            );
+        }
+        if config.client_auth.is_some() {
+            return Err("Client certificates aren't supported with native-tls".into());
+        }
+        if !config.alpn_protocols.is_empty() {
+            return Err("ALPN isn't supported with native-tls".into());
+        }
+        if !config.cipher_suites.is_empty() {
+            return Err("Choosing the cipher suites isn't supported with native-tls".into());
+        }
+        if config.min_version == Some(TlsVersion::Tls1_3) {
+            return Err("Requiring TLS 1.3 isn't supported with native-tls".into());
+        }
+
+        let certificates = config.certificate;
+        let private_key = Zeroizing::new(config.private_key);
         let identity = native_tls::Identity::from_pkcs8(&certificates, &private_key)?;
-        let acceptor = native_tls::TlsAcceptor::new(identity)?;
+        let acceptor = native_tls::TlsAcceptor::builder(identity)
+            .min_protocol_version(config.min_version.map(protocol))
+            .max_protocol_version(config.max_version.and_then(|max| match max {
+                // native-tls doesn't know about TLS 1.3, which is the highest version anyway
+                TlsVersion::Tls1_3 => None,
+                max => Some(protocol(max)),
+            }))
+            .build()?;
         Ok(Self(acceptor))
     }
 
     pub fn accept(
         &self,
         stream: Connection,
+        deadline: Option<Instant>,
     ) -> Result<NativeTlsStream, Box<dyn Error + Send + Sync + 'static>> {
-        let stream = self.0.accept(stream)?;
-        Ok(NativeTlsStream(Arc::new(Mutex::new(stream))))
+        use native_tls::HandshakeError;
+
+        let mut result = self.0.accept(MemoryTransport::default());
+        loop {
+            match result {
+                Ok(mut session) => {
+                    session.get_mut().send_to(&stream)?;
+                    return Ok(SplitTlsStream::new(session, stream));
+                }
+                Err(HandshakeError::WouldBlock(mut handshake)) => {
+                    handshake.get_mut().send_to(&stream)?;
+                    handshake.get_mut().receive_handshake(&stream, deadline)?;
+                    result = handshake.handshake();
+                }
+                Err(HandshakeError::Failure(err)) => return Err(err.into()),
+            }
+        }
     }
 }
 
@@ -82,3 +94,11 @@ impl From<NativeTlsStream> for RefinedStream {
         RefinedStream::Https(stream)
     }
 }
+
+fn protocol(version: TlsVersion) -> native_tls::Protocol {
+    match version {
+        TlsVersion::Tls1_0 => native_tls::Protocol::Tlsv10,
+        TlsVersion::Tls1_1 => native_tls::Protocol::Tlsv11,
+        TlsVersion::Tls1_2 | TlsVersion::Tls1_3 => native_tls::Protocol::Tlsv12,
+    }
+}
@@ -1,93 +1,98 @@
 use crate::connection::Connection;
+use crate::ssl::is_pem;
+use crate::ssl::split::{MemoryTransport, SplitTlsStream, TlsSession};
 use crate::util::refined_tcp_stream::Stream as RefinedStream;
+use crate::{SslConfig, TlsInfo, TlsVersion};
+use std::collections::HashMap;
 use std::error::Error;
-use std::io::{Read, Write};
-use std::net::{Shutdown, SocketAddr};
-use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use zeroize::Zeroizing;
 
-pub(crate) struct OpenSslStream {
-    inner: openssl::ssl::SslStream<Connection>,
-}
-
-/// An OpenSSL stream which has been split into two mutually exclusive streams (e.g. for read / write)
-pub(crate) struct SplitOpenSslStream(Arc<Mutex<OpenSslStream>>);
+/// An OpenSSL stream which can be read from and written to concurrently.
+pub(crate) type OpenSslStream = SplitTlsStream<OpenSslSession>;
 
-// These struct methods form the implict contract for swappable TLS implementations
-impl SplitOpenSslStream {
-    pub(crate) fn peer_addr(&mut self) -> std::io::Result<Option<SocketAddr>> {
-        self.0.lock().unwrap().inner.get_mut().peer_addr()
-    }
+pub(crate) type OpenSslSession = openssl::ssl::SslStream<MemoryTransport>;
 
-    pub(crate) fn shutdown(&mut self, how: Shutdown) -> std::io::Result<()> {
-        self.0.lock().unwrap().inner.get_mut().shutdown(how)
+impl TlsSession for OpenSslSession {
+    fn transport(&mut self) -> &mut MemoryTransport {
+        self.get_mut()
     }
 }
 
-impl Clone for SplitOpenSslStream {
-    fn clone(&self) -> Self {
-        Self(self.0.clone())
-    }
-}
+impl OpenSslStream {
+    pub(crate) fn peer_certificates(&self) -> Option<Vec<Vec<u8>>> {
+        let session = self.session();
+        let ssl = session.ssl();
+        let leaf = ssl.peer_certificate()?.to_der().ok()?;
 
-impl Read for SplitOpenSslStream {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.0.lock().unwrap().read(buf)
+        // on the server side, the chain doesn't include the leaf certificate
+        let mut chain = vec![leaf];
+        if let Some(others) = ssl.peer_cert_chain() {
+            for certificate in others {
+                let certificate = certificate.to_der().ok()?;
+                if certificate != chain[0] {
+                    chain.push(certificate);
+                }
+            }
+        }
+        Some(chain)
     }
-}
 
-impl Write for SplitOpenSslStream {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.0.lock().unwrap().write(buf)
-    }
+    pub(crate) fn tls_info(&self) -> Option<TlsInfo> {
+        use openssl::ssl::NameType;
 
-    fn flush(&mut self) -> std::io::Result<()> {
-        self.0.lock().unwrap().flush()
-    }
-}
+        let session = self.session();
+        let ssl = session.ssl();
+        let version = match ssl.version_str() {
+            "TLSv1" => TlsVersion::Tls1_0,
+            "TLSv1.1" => TlsVersion::Tls1_1,
+            "TLSv1.2" => TlsVersion::Tls1_2,
+            "TLSv1.3" => TlsVersion::Tls1_3,
+            _ => return None,
+        };
 
-impl Read for OpenSslStream {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.inner.read(buf)
-    }
-}
-
-impl Write for OpenSslStream {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.inner.write(buf)
-    }
-
-    fn flush(&mut self) -> std::io::Result<()> {
-        self.inner.flush()
+        Some(TlsInfo {
+            version,
+            cipher_suite: ssl.current_cipher()?.name().to_owned(),
+            server_name: ssl.servername(NameType::HOST_NAME).map(str::to_owned),
+            alpn_protocol: ssl.selected_alpn_protocol().map(<[u8]>::to_vec),
+        })
     }
 }
 
 pub(crate) struct OpenSslContext(openssl::ssl::SslContext);
 
 impl OpenSslContext {
-    pub fn from_pem(
-        certificates: Vec<u8>,
-        private_key: Zeroizing<Vec<u8>>,
-    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
-        use openssl::pkey::PKey;
-        use openssl::ssl::{self, SslVerifyMode};
-        use openssl::x509::X509;
-
-        let mut ctx = openssl::ssl::SslContext::builder(ssl::SslMethod::tls())?;
-        ctx.set_cipher_list("DEFAULT")?;
-        let certificate_chain = X509::stack_from_pem(&certificates)?;
-        if certificate_chain.is_empty() {
-            return Err("Couldn't extract certificate chain from config.".into());
+    pub fn from_config(mut config: SslConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        use openssl::ssl::{NameType, SniError};
+
+        let mut server_names = HashMap::with_capacity(config.server_names.len());
+        for (name, certificate) in std::mem::take(&mut config.server_names) {
+            let ctx = context_builder(
+                certificate.certificate,
+                Zeroizing::new(certificate.private_key),
+                &config,
+            )?;
+            server_names.insert(name.to_ascii_lowercase(), ctx.build());
         }
-        // The leaf certificate must always be first in the PEM file
-        ctx.set_certificate(&certificate_chain[0])?;
-        for chain_cert in certificate_chain.into_iter().skip(1) {
-            ctx.add_extra_chain_cert(chain_cert)?;
+
+        let certificate = std::mem::take(&mut config.certificate);
+        let private_key = Zeroizing::new(std::mem::take(&mut config.private_key));
+        let mut ctx = context_builder(certificate, private_key, &config)?;
+        if !server_names.is_empty() {
+            // switching to the context of the requested name, if any, before the certificate
+            // is sent
+            ctx.set_servername_callback(move |ssl, _alert| {
+                let name = ssl
+                    .servername(NameType::HOST_NAME)
+                    .map(|name| name.to_ascii_lowercase());
+                if let Some(ctx) = name.and_then(|name| server_names.get(&name)) {
+                    ssl.set_ssl_context(ctx)
+                        .map_err(|_| SniError::ALERT_FATAL)?;
+                }
+                Ok(())
+            });
         }
-        let key = PKey::private_key_from_pem(&private_key)?;
-        ctx.set_private_key(&key)?;
-        ctx.set_verify(SslVerifyMode::NONE);
-        ctx.check_private_key()?;
 
         Ok(Self(ctx.build()))
     }
@@ -95,16 +100,158 @@ impl OpenSslContext {
     pub fn accept(
         &self,
         stream: Connection,
+        deadline: Option<Instant>,
     ) -> Result<OpenSslStream, Box<dyn Error + Send + Sync + 'static>> {
-        use openssl::ssl::Ssl;
-        let session = Ssl::new(&self.0).expect("Failed to create new OpenSSL session");
-        let stream = session.accept(stream)?;
-        Ok(OpenSslStream { inner: stream })
+        use openssl::ssl::{HandshakeError, Ssl};
+
+        let mut result = Ssl::new(&self.0)?.accept(MemoryTransport::default());
+        loop {
+            match result {
+                Ok(mut session) => {
+                    session.get_mut().send_to(&stream)?;
+                    return Ok(SplitTlsStream::new(session, stream));
+                }
+                Err(HandshakeError::WouldBlock(mut handshake)) => {
+                    handshake.get_mut().send_to(&stream)?;
+                    handshake.get_mut().receive_handshake(&stream, deadline)?;
+                    result = handshake.handshake();
+                }
+                Err(HandshakeError::Failure(mut handshake)) => {
+                    // sending the alert, if any
+                    let _ = handshake.get_mut().send_to(&stream);
+                    return Err(handshake.into_error().into());
+                }
+                Err(HandshakeError::SetupFailure(err)) => return Err(err.into()),
+            }
+        }
     }
 }
 
 impl From<OpenSslStream> for RefinedStream {
     fn from(stream: OpenSslStream) -> Self {
-        RefinedStream::Https(SplitOpenSslStream(Arc::new(Mutex::new(stream))))
+        RefinedStream::Https(stream)
+    }
+}
+
+fn context_builder(
+    certificates: Vec<u8>,
+    private_key: Zeroizing<Vec<u8>>,
+    config: &SslConfig,
+) -> Result<openssl::ssl::SslContextBuilder, Box<dyn Error + Send + Sync>> {
+    use openssl::pkey::PKey;
+    use openssl::ssl::{self, AlpnError, SslVerifyMode};
+
+    let mut ctx = openssl::ssl::SslContext::builder(ssl::SslMethod::tls())?;
+    ctx.set_min_proto_version(config.min_version.map(ssl_version))?;
+    ctx.set_max_proto_version(config.max_version.map(ssl_version))?;
+
+    // OpenSSL configures the TLS 1.3 suites separately from the older ones
+    let (tls13_suites, suites): (Vec<&str>, Vec<&str>) = config
+        .cipher_suites
+        .iter()
+        .map(String::as_str)
+        .partition(|name| name.starts_with("TLS_"));
+    if suites.is_empty() {
+        ctx.set_cipher_list("DEFAULT")?;
+    } else {
+        ctx.set_cipher_list(&suites.join(":"))?;
+    }
+    if !tls13_suites.is_empty() {
+        ctx.set_ciphersuites(&tls13_suites.join(":"))?;
+    }
+
+    let certificate_chain = parse_certificates(&certificates)?;
+    if certificate_chain.is_empty() {
+        return Err("Couldn't extract certificate chain from config.".into());
+    }
+    // The leaf certificate must always be first in the PEM file
+    ctx.set_certificate(&certificate_chain[0])?;
+    for chain_cert in certificate_chain.into_iter().skip(1) {
+        ctx.add_extra_chain_cert(chain_cert)?;
+    }
+    let key = if is_pem(&private_key) {
+        PKey::private_key_from_pem(&private_key)?
+    } else {
+        PKey::private_key_from_der(&private_key)?
+    };
+    ctx.set_private_key(&key)?;
+    ctx.check_private_key()?;
+
+    match &config.client_auth {
+        None => ctx.set_verify(SslVerifyMode::NONE),
+        Some(client_auth) => {
+            let authorities = parse_certificates(&client_auth.ca_certificates)?;
+            if authorities.is_empty() {
+                return Err("Couldn't extract client certificate authorities from config.".into());
+            }
+            for authority in authorities {
+                ctx.add_client_ca(&authority)?;
+                ctx.cert_store_mut().add_cert(authority)?;
+            }
+
+            let mut mode = SslVerifyMode::PEER;
+            if client_auth.required {
+                mode |= SslVerifyMode::FAIL_IF_NO_PEER_CERT;
+            }
+            ctx.set_verify(mode);
+            // resuming a session fails without it when the peer is verified
+            ctx.set_session_id_context(b"tiny_http")?;
+        }
+    }
+
+    if !config.alpn_protocols.is_empty() {
+        let protocols = config.alpn_protocols.clone();
+        ctx.set_alpn_select_callback(move |_, client_protocols| {
+            let client_protocols = split_alpn_protocols(client_protocols);
+            protocols
+                .iter()
+                .find_map(|protocol| {
+                    client_protocols
+                        .iter()
+                        .find(|client_protocol| **client_protocol == &protocol[..])
+                        .copied()
+                })
+                .ok_or(AlpnError::NOACK)
+        });
+    }
+
+    Ok(ctx)
+}
+
+/// Parses certificates in the PEM format, or a single certificate in the DER format.
+fn parse_certificates(
+    certificates: &[u8],
+) -> Result<Vec<openssl::x509::X509>, Box<dyn Error + Send + Sync>> {
+    use openssl::x509::X509;
+
+    if is_pem(certificates) {
+        Ok(X509::stack_from_pem(certificates)?)
+    } else {
+        Ok(vec![X509::from_der(certificates)?])
+    }
+}
+
+fn ssl_version(version: TlsVersion) -> openssl::ssl::SslVersion {
+    use openssl::ssl::SslVersion;
+
+    match version {
+        TlsVersion::Tls1_0 => SslVersion::TLS1,
+        TlsVersion::Tls1_1 => SslVersion::TLS1_1,
+        TlsVersion::Tls1_2 => SslVersion::TLS1_2,
+        TlsVersion::Tls1_3 => SslVersion::TLS1_3,
+    }
+}
+
+/// Splits a list of protocols in the ALPN wire format, where each one is preceded by its length.
+fn split_alpn_protocols(mut list: &[u8]) -> Vec<&[u8]> {
+    let mut protocols = Vec::new();
+    while let Some((&len, rest)) = list.split_first() {
+        if rest.len() < usize::from(len) {
+            break;
+        }
+        let (protocol, rest) = rest.split_at(usize::from(len));
+        protocols.push(protocol);
+        list = rest;
     }
+    protocols
 }
@@ -1,103 +1,98 @@
 use crate::connection::Connection;
+use crate::ssl::is_pem;
+use crate::ssl::split::{MemoryTransport, SplitTlsStream, TlsSession};
 use crate::util::refined_tcp_stream::Stream as RefinedStream;
+use crate::{ClientAuth, SslConfig, TlsInfo, TlsVersion};
+use rustls::server::{
+    AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, ClientHello,
+    ResolvesServerCert,
+};
+use rustls::sign::CertifiedKey;
+use std::collections::HashMap;
 use std::error::Error;
-use std::io::{Read, Write};
-use std::net::{Shutdown, SocketAddr};
-use std::sync::{Arc, Mutex};
+use std::io::ErrorKind;
+use std::sync::Arc;
+use std::time::Instant;
 use zeroize::Zeroizing;
 
-/// A wrapper around an owned Rustls connection and corresponding stream.
-///
-/// Uses an internal Mutex to permit disparate reader & writer threads to access the stream independently.
-pub(crate) struct RustlsStream(
-    Arc<Mutex<rustls::StreamOwned<rustls::ServerConnection, Connection>>>,
-);
+/// A Rustls connection which can be read from and written to concurrently.
+pub(crate) type RustlsStream = SplitTlsStream<RustlsSession>;
 
-impl RustlsStream {
-    pub(crate) fn peer_addr(&mut self) -> std::io::Result<Option<SocketAddr>> {
-        self.0
-            .lock()
-            .expect("Failed to lock SSL stream mutex")
-            .sock
-            .peer_addr()
-    }
-
-    pub(crate) fn shutdown(&mut self, how: Shutdown) -> std::io::Result<()> {
-        self.0
-            .lock()
-            .expect("Failed to lock SSL stream mutex")
-            .sock
-            .shutdown(how)
-    }
-}
+pub(crate) type RustlsSession = rustls::StreamOwned<rustls::ServerConnection, MemoryTransport>;
 
-impl Clone for RustlsStream {
-    fn clone(&self) -> Self {
-        Self(self.0.clone())
+impl TlsSession for RustlsSession {
+    fn transport(&mut self) -> &mut MemoryTransport {
+        &mut self.sock
     }
 }
 
-impl Read for RustlsStream {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.0
-            .lock()
-            .expect("Failed to lock SSL stream mutex")
-            .read(buf)
+impl RustlsStream {
+    pub(crate) fn peer_certificates(&self) -> Option<Vec<Vec<u8>>> {
+        let session = self.session();
+        let chain = session.conn.peer_certificates()?;
+        Some(
+            chain
+                .iter()
+                .map(|certificate| certificate.0.clone())
+                .collect(),
+        )
     }
-}
 
-impl Write for RustlsStream {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.0
-            .lock()
-            .expect("Failed to lock SSL stream mutex")
-            .write(buf)
-    }
+    pub(crate) fn tls_info(&self) -> Option<TlsInfo> {
+        let session = self.session();
+        let version = match session.conn.protocol_version()? {
+            rustls::ProtocolVersion::TLSv1_2 => TlsVersion::Tls1_2,
+            rustls::ProtocolVersion::TLSv1_3 => TlsVersion::Tls1_3,
+            _ => return None,
+        };
 
-    fn flush(&mut self) -> std::io::Result<()> {
-        self.0
-            .lock()
-            .expect("Failed to lock SSL stream mutex")
-            .flush()
+        Some(TlsInfo {
+            version,
+            cipher_suite: format!("{:?}", session.conn.negotiated_cipher_suite()?.suite()),
+            server_name: session.conn.sni_hostname().map(str::to_owned),
+            alpn_protocol: session.conn.alpn_protocol().map(<[u8]>::to_vec),
+        })
     }
 }
 
 pub(crate) struct RustlsContext(Arc<rustls::ServerConfig>);
 
 impl RustlsContext {
-    pub(crate) fn from_pem(
-        certificates: Vec<u8>,
-        private_key: Zeroizing<Vec<u8>>,
-    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
-        let certificate_chain: Vec<rustls::Certificate> =
-            rustls_pemfile::certs(&mut certificates.as_slice())?
-                .into_iter()
-                .map(|bytes| rustls::Certificate(bytes))
-                .collect();
-
-        if certificate_chain.is_empty() {
-            return Err("Couldn't extract certificate chain from config.".into());
-        }
+    pub(crate) fn from_config(config: SslConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let cipher_suites = cipher_suites(&config.cipher_suites)?;
+        let protocol_versions = protocol_versions(&config)?;
+        let default = certified_key(config.certificate, Zeroizing::new(config.private_key))?;
 
-        let private_key = rustls::PrivateKey({
-            let pkcs8_keys = rustls_pemfile::pkcs8_private_keys(
-                &mut private_key.clone().as_slice(),
-            )
-            .expect("file contains invalid pkcs8 private key (encrypted keys are not supported)");
-
-            if let Some(pkcs8_key) = pkcs8_keys.first() {
-                pkcs8_key.clone()
-            } else {
-                let rsa_keys = rustls_pemfile::rsa_private_keys(&mut private_key.as_slice())
-                    .expect("file contains invalid rsa private key");
-                rsa_keys[0].clone()
-            }
-        });
+        let mut server_names = HashMap::with_capacity(config.server_names.len());
+        for (name, certificate) in config.server_names {
+            let key = certified_key(
+                certificate.certificate,
+                Zeroizing::new(certificate.private_key),
+            )?;
+            server_names.insert(name.to_ascii_lowercase(), Arc::new(key));
+        }
 
         let tls_conf = rustls::ServerConfig::builder()
-            .with_safe_defaults()
-            .with_no_client_auth()
-            .with_single_cert(certificate_chain, private_key)?;
+            .with_cipher_suites(&cipher_suites)
+            .with_safe_default_kx_groups()
+            .with_protocol_versions(&protocol_versions)?;
+        let tls_conf = match config.client_auth {
+            None => tls_conf.with_no_client_auth(),
+            Some(client_auth) => {
+                let authorities = client_authorities(&client_auth)?;
+                let verifier = if client_auth.required {
+                    AllowAnyAuthenticatedClient::new(authorities)
+                } else {
+                    AllowAnyAnonymousOrAuthenticatedClient::new(authorities)
+                };
+                tls_conf.with_client_cert_verifier(verifier)
+            }
+        };
+        let mut tls_conf = tls_conf.with_cert_resolver(Arc::new(SniResolver {
+            default: Arc::new(default),
+            server_names,
+        }));
+        tls_conf.alpn_protocols = config.alpn_protocols;
 
         Ok(Self(Arc::new(tls_conf)))
     }
@@ -105,11 +100,26 @@ impl RustlsContext {
     pub(crate) fn accept(
         &self,
         stream: Connection,
+        deadline: Option<Instant>,
     ) -> Result<RustlsStream, Box<dyn Error + Send + Sync + 'static>> {
         let connection = rustls::ServerConnection::new(self.0.clone())?;
-        Ok(RustlsStream(Arc::new(Mutex::new(
-            rustls::StreamOwned::new(connection, stream),
-        ))))
+        let mut session = rustls::StreamOwned::new(connection, MemoryTransport::default());
+        // completing the handshake right away rather than on the first read, like the other
+        // implementations do
+        while session.conn.is_handshaking() {
+            let result = session.conn.complete_io(&mut session.sock);
+            // sending the alert as well if the handshake failed
+            session.sock.send_to(&stream)?;
+            match result {
+                Ok(_) => (),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    session.sock.receive_handshake(&stream, deadline)?
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        session.sock.send_to(&stream)?;
+        Ok(SplitTlsStream::new(session, stream))
     }
 }
 
@@ -118,3 +128,129 @@ impl From<RustlsStream> for RefinedStream {
         Self::Https(stream)
     }
 }
+
+/// Chooses the certificate to present from the server name sent by the client.
+struct SniResolver {
+    default: Arc<CertifiedKey>,
+    server_names: HashMap<String, Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let key = client_hello
+            .server_name()
+            .and_then(|name| self.server_names.get(&name.to_ascii_lowercase()))
+            .unwrap_or(&self.default);
+        Some(key.clone())
+    }
+}
+
+/// Looks up the cipher suites with the given names, or returns the default ones if there are none.
+fn cipher_suites(
+    names: &[String],
+) -> Result<Vec<rustls::SupportedCipherSuite>, Box<dyn Error + Send + Sync>> {
+    if names.is_empty() {
+        return Ok(rustls::DEFAULT_CIPHER_SUITES.to_vec());
+    }
+
+    names
+        .iter()
+        .map(|name| {
+            rustls::ALL_CIPHER_SUITES
+                .iter()
+                .copied()
+                .find(|suite| {
+                    // rustls prefixes the TLS 1.3 suites with `TLS13_` instead of `TLS_`
+                    let suite_name = format!("{:?}", suite.suite());
+                    suite_name == *name || suite_name.replacen("TLS13_", "TLS_", 1) == *name
+                })
+                .ok_or_else(|| format!("Unsupported cipher suite: {}", name).into())
+        })
+        .collect()
+}
+
+/// Returns the protocol versions supported by rustls between the minimum and the maximum.
+fn protocol_versions(
+    config: &SslConfig,
+) -> Result<Vec<&'static rustls::SupportedProtocolVersion>, Box<dyn Error + Send + Sync>> {
+    let versions: Vec<_> = rustls::ALL_VERSIONS
+        .iter()
+        .copied()
+        .filter(|version| {
+            let version = match version.version {
+                rustls::ProtocolVersion::TLSv1_2 => TlsVersion::Tls1_2,
+                _ => TlsVersion::Tls1_3,
+            };
+            config.min_version.map_or(true, |min| version >= min)
+                && config.max_version.map_or(true, |max| version <= max)
+        })
+        .collect();
+
+    if versions.is_empty() {
+        return Err("None of the allowed TLS versions is supported by rustls.".into());
+    }
+    Ok(versions)
+}
+
+fn client_authorities(
+    client_auth: &ClientAuth,
+) -> Result<rustls::RootCertStore, Box<dyn Error + Send + Sync>> {
+    let mut authorities = rustls::RootCertStore::empty();
+    for authority in parse_certificates(&client_auth.ca_certificates)? {
+        authorities.add(&authority)?;
+    }
+    if authorities.is_empty() {
+        return Err("Couldn't extract client certificate authorities from config.".into());
+    }
+    Ok(authorities)
+}
+
+fn certified_key(
+    certificates: Vec<u8>,
+    private_key: Zeroizing<Vec<u8>>,
+) -> Result<CertifiedKey, Box<dyn Error + Send + Sync>> {
+    let certificate_chain = parse_certificates(&certificates)?;
+    if certificate_chain.is_empty() {
+        return Err("Couldn't extract certificate chain from config.".into());
+    }
+
+    let private_key = parse_private_key(&private_key)?;
+    let signing_key = rustls::sign::any_supported_type(&private_key)
+        .map_err(|_| "Unsupported private key type in config.")?;
+    Ok(CertifiedKey::new(certificate_chain, signing_key))
+}
+
+/// Parses certificates in the PEM format, or a single certificate in the DER format.
+fn parse_certificates(
+    certificates: &[u8],
+) -> Result<Vec<rustls::Certificate>, Box<dyn Error + Send + Sync>> {
+    if !is_pem(certificates) {
+        return Ok(vec![rustls::Certificate(certificates.to_vec())]);
+    }
+
+    Ok(rustls_pemfile::certs(&mut &certificates[..])?
+        .into_iter()
+        .map(rustls::Certificate)
+        .collect())
+}
+
+/// Parses a PKCS#8, RSA or SEC1 private key, in the PEM or the DER format.
+fn parse_private_key(
+    private_key: &[u8],
+) -> Result<rustls::PrivateKey, Box<dyn Error + Send + Sync>> {
+    if !is_pem(private_key) {
+        // rustls figures out the format of the key by itself
+        return Ok(rustls::PrivateKey(private_key.to_vec()));
+    }
+
+    for item in rustls_pemfile::read_all(&mut &private_key[..])? {
+        match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => return Ok(rustls::PrivateKey(key)),
+            _ => (),
+        }
+    }
+
+    Err("Couldn't extract private key from config.".into())
+}
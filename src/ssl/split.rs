@@ -0,0 +1,332 @@
+//! A TLS stream that can be read from and written to at the same time by different threads.
+//!
+//! The TLS implementations are given a `MemoryTransport` instead of the socket. The data received
+//! from the socket is fed to it, and the data it accumulates is written to the socket afterwards.
+//! This way the session is only locked while processing data, never while waiting for the
+//! client, and a response can be sent while the next request is being received.
+
+use crate::connection::Connection;
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult, Write};
+use std::mem;
+use std::net::{Shutdown, SocketAddr};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Transport of a TLS session which never blocks.
+#[derive(Default)]
+pub(crate) struct MemoryTransport {
+    // data received from the client, not processed by the session yet
+    incoming: Vec<u8>,
+    // data produced by the session, not sent to the client yet
+    outgoing: Vec<u8>,
+    // true once the client has closed its side of the connection
+    eof: bool,
+    // number of times data was received from the socket, to know if some arrived in between
+    receptions: u64,
+}
+
+impl MemoryTransport {
+    /// Receives data from the socket, blocking until some is available.
+    ///
+    /// Returns the number of bytes received, zero meaning that the client closed its side of
+    /// the connection.
+    pub(crate) fn receive_from(&mut self, socket: &Connection) -> IoResult<usize> {
+        let mut buf = [0; 8 * 1024];
+        let len = (&mut &*socket).read(&mut buf)?;
+        self.incoming.extend_from_slice(&buf[..len]);
+        self.eof = len == 0;
+        Ok(len)
+    }
+
+    /// Writes everything the session has produced so far to the socket.
+    pub(crate) fn send_to(&mut self, socket: &Connection) -> IoResult<()> {
+        let outgoing = mem::take(&mut self.outgoing);
+        (&mut &*socket).write_all(&outgoing)
+    }
+
+    /// Receives data from the socket during a handshake, for which the end of the connection
+    /// is an error.
+    ///
+    /// If `deadline` is `Some`, the time left until then is applied to the reads and writes of
+    /// the socket, so that the whole handshake can't take longer.
+    pub(crate) fn receive_handshake(
+        &mut self,
+        socket: &Connection,
+        deadline: Option<Instant>,
+    ) -> IoResult<()> {
+        if let Some(deadline) = deadline {
+            let now = Instant::now();
+            if deadline <= now {
+                return Err(IoError::new(ErrorKind::TimedOut, "TLS handshake timed out"));
+            }
+            socket.set_read_timeout(Some(deadline - now))?;
+            socket.set_write_timeout(Some(deadline - now))?;
+        }
+
+        match self.receive_from(socket)? {
+            0 => Err(IoError::new(
+                ErrorKind::UnexpectedEof,
+                "Connection closed during the TLS handshake",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Read for MemoryTransport {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if self.incoming.is_empty() {
+            return if self.eof {
+                Ok(0)
+            } else {
+                Err(ErrorKind::WouldBlock.into())
+            };
+        }
+
+        let len = buf.len().min(self.incoming.len());
+        buf[..len].copy_from_slice(&self.incoming[..len]);
+        self.incoming.drain(..len);
+        Ok(len)
+    }
+}
+
+impl Write for MemoryTransport {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.outgoing.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+/// A TLS session running over a `MemoryTransport`.
+///
+/// Reading and writing return `WouldBlock` errors when the session needs more data from the
+/// client.
+pub(crate) trait TlsSession: Read + Write + Send {
+    fn transport(&mut self) -> &mut MemoryTransport;
+}
+
+/// A TLS stream whose clones can be used concurrently, typically one for reading the requests
+/// and one for writing the responses.
+pub(crate) struct SplitTlsStream<S> {
+    shared: Arc<Shared<S>>,
+}
+
+struct Shared<S> {
+    session: Mutex<S>,
+    // notified when data from the client is fed to the session, or when receiving it failed
+    received: Condvar,
+    socket: Connection,
+    // held while receiving from the socket, so that the data is fed to the session in order
+    reading: Mutex<()>,
+    // held while writing to the socket, so that the data of the session is sent in order
+    writing: Mutex<()>,
+}
+
+impl<S> Clone for SplitTlsStream<S> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+// These struct methods form the implict contract for swappable TLS implementations
+impl<S: TlsSession> SplitTlsStream<S> {
+    /// Wraps a session whose handshake is complete.
+    pub(crate) fn new(session: S, socket: Connection) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                session: Mutex::new(session),
+                received: Condvar::new(),
+                socket,
+                reading: Mutex::new(()),
+                writing: Mutex::new(()),
+            }),
+        }
+    }
+
+    pub(crate) fn peer_addr(&mut self) -> IoResult<Option<SocketAddr>> {
+        self.shared.socket.peer_addr()
+    }
+
+    pub(crate) fn shutdown(&mut self, how: Shutdown) -> IoResult<()> {
+        self.shared.socket.shutdown(how)
+    }
+
+    pub(crate) fn set_read_timeout(&mut self, timeout: Option<Duration>) -> IoResult<()> {
+        self.shared.socket.set_read_timeout(timeout)
+    }
+
+    /// Gives access to the session, to query the parameters of the connection.
+    pub(crate) fn session(&self) -> MutexGuard<'_, S> {
+        self.shared.session.lock().unwrap()
+    }
+
+    /// Sends what the session has produced, releasing the lock of the session before writing.
+    fn send(&self, mut session: MutexGuard<'_, S>) -> IoResult<()> {
+        let outgoing = mem::take(&mut session.transport().outgoing);
+        if outgoing.is_empty() {
+            return Ok(());
+        }
+
+        let _writing = self.shared.writing.lock().unwrap();
+        drop(session);
+        (&mut &self.shared.socket).write_all(&outgoing)
+    }
+
+    /// Receives data from the client and feeds it to the session, blocking until some is
+    /// available. `reading` is the lock of the socket for receiving.
+    fn receive(&self, reading: MutexGuard<'_, ()>) -> IoResult<()> {
+        let mut received = [0; 8 * 1024];
+        let result = (&mut &self.shared.socket).read(&mut received);
+
+        let mut session = self.session();
+        let transport = session.transport();
+        if let Ok(len) = result {
+            transport.incoming.extend_from_slice(&received[..len]);
+            transport.eof = len == 0;
+        }
+        transport.receptions += 1;
+        self.shared.received.notify_all();
+
+        // released before the session, so that the threads waiting for data are sure to be
+        // notified if they can't receive it themselves
+        drop(reading);
+        result.map(|_| ())
+    }
+}
+
+impl<S: TlsSession> Read for SplitTlsStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        loop {
+            let mut session = self.session();
+            let result = session.read(buf);
+            let receptions = session.transport().receptions;
+            self.send(session)?;
+            match result {
+                Err(ref err) if err.kind() == ErrorKind::WouldBlock => (),
+                result => return result,
+            }
+
+            // waiting for the client without holding the lock of the session, unless the
+            // writing side received some data in the meantime
+            let reading = self.shared.reading.lock().unwrap();
+            let mut session = self.session();
+            if session.transport().receptions == receptions {
+                drop(session);
+                self.receive(reading)?;
+            } else {
+                // the threads waiting for this side to receive data must try again
+                self.shared.received.notify_all();
+                drop(reading);
+            }
+        }
+    }
+}
+
+impl<S: TlsSession> Write for SplitTlsStream<S> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        loop {
+            let mut session = self.session();
+            match session.write(buf) {
+                Err(ref err) if err.kind() == ErrorKind::WouldBlock => {
+                    // the session needs data from the client first, for instance during a
+                    // renegotiation
+                    if !session.transport().outgoing.is_empty() {
+                        // the reading side may need the session while the socket is written to,
+                        // and some data may have been received in the meantime
+                        self.send(session)?;
+                        continue;
+                    }
+
+                    // the data is received by the reading side of the stream if it is waiting
+                    // for the client, otherwise by this side
+                    match self.shared.reading.try_lock() {
+                        Ok(reading) => {
+                            drop(session);
+                            self.receive(reading)?;
+                        }
+                        Err(_) => drop(self.shared.received.wait(session).unwrap()),
+                    }
+                }
+                result => {
+                    self.send(session)?;
+                    return result;
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        let mut session = self.session();
+        session.flush()?;
+        self.send(session)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::{MemoryTransport, SplitTlsStream, TlsSession};
+    use crate::connection::Connection;
+    use std::io::{ErrorKind, Read, Result as IoResult, Write};
+    use std::os::unix::net::UnixStream;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    /// A session that can't write anything before it has received some data, like a session
+    /// in the middle of a renegotiation.
+    #[derive(Default)]
+    struct BlockedSession {
+        transport: MemoryTransport,
+    }
+
+    impl Read for BlockedSession {
+        fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+            self.transport.read(buf)
+        }
+    }
+
+    impl Write for BlockedSession {
+        fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+            if self.transport.incoming.is_empty() {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+            self.transport.incoming.clear();
+            self.transport.write(buf)
+        }
+
+        fn flush(&mut self) -> IoResult<()> {
+            Ok(())
+        }
+    }
+
+    impl TlsSession for BlockedSession {
+        fn transport(&mut self) -> &mut MemoryTransport {
+            &mut self.transport
+        }
+    }
+
+    #[test]
+    fn test_write_without_reader() {
+        let (socket, mut client) = UnixStream::pair().unwrap();
+        let stream = SplitTlsStream::new(BlockedSession::default(), Connection::from(socket));
+        client.write_all(b"x").unwrap();
+
+        // nothing reads from the stream, so the writing side must receive the data itself
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut stream = stream;
+            sender.send(stream.write_all(b"hello").is_ok()).unwrap();
+        });
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).unwrap());
+
+        let mut received = [0; 5];
+        client.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"hello");
+    }
+}
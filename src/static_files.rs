@@ -0,0 +1,364 @@
+//! Serving the files of a directory.
+//!
+//! ```no_run
+//! use tiny_http::static_files::StaticFiles;
+//!
+//! let server = tiny_http::Server::http("0.0.0.0:8000").unwrap();
+//! let files = StaticFiles::new("/static", "./public");
+//!
+//! for request in server.incoming_requests() {
+//!     let response = match files.response(&request) {
+//!         Some(response) => response,
+//!         None => tiny_http::Response::empty(404).boxed(),
+//!     };
+//!     let _ = request.respond(response);
+//! }
+//! ```
+
+use crate::{Header, Method, Request, Response, ResponseBox};
+use httpdate::HttpDate;
+use std::fs::{self, File, Metadata};
+use std::io::{Error as IoError, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Maps the URLs starting with a prefix to the files of a directory.
+///
+/// The path following the prefix is percent-decoded, and its `.` and `..` segments are
+/// resolved without ever going above the root directory. Symbolic links inside the directory
+/// are followed.
+///
+/// The responses have a `Content-Type` guessed from the file extension, see `content_type`,
+/// as well as `Last-Modified` and `ETag` headers. Conditional requests using `If-None-Match` or
+/// `If-Modified-Since` get a `304 Not Modified` response when the file hasn't changed, and range
+/// requests are supported as described in [`Response::from_file`].
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    // without the trailing slash, empty for the root
+    prefix: String,
+    root: PathBuf,
+    index_files: Vec<String>,
+}
+
+impl StaticFiles {
+    /// Serves the files of `root` for the URLs starting with `prefix`, for example `/static`.
+    pub fn new<P>(prefix: &str, root: P) -> StaticFiles
+    where
+        P: Into<PathBuf>,
+    {
+        StaticFiles {
+            prefix: prefix.trim_end_matches('/').to_owned(),
+            root: root.into(),
+            index_files: vec!["index.html".to_owned()],
+        }
+    }
+
+    /// Sets the names of the files looked for, in this order, when the URL is a directory.
+    ///
+    /// The default is `index.html`. Without any index file, requesting a directory results in
+    /// a `404 Not Found` response.
+    pub fn with_index_files(mut self, names: &[&str]) -> StaticFiles {
+        self.index_files = names.iter().map(|name| (*name).to_owned()).collect();
+        self
+    }
+
+    /// Builds the response to a request, or returns `None` if its URL doesn't start with the
+    /// prefix.
+    ///
+    /// Missing files get a `404 Not Found` response, and methods other than `GET` and `HEAD` a
+    /// `405 Method Not Allowed` response. A URL of a directory that doesn't end with a slash
+    /// is redirected to the same URL with a slash, so that relative links in the index file
+    /// work.
+    pub fn response(&self, request: &Request) -> Option<ResponseBox> {
+        let url = request.url();
+        let (path, query) = match url.find('?') {
+            Some(pos) => url.split_at(pos),
+            None => (url, ""),
+        };
+        let relative = self.strip_prefix(path)?;
+
+        match *request.method() {
+            Method::Get | Method::Head => (),
+            _ => {
+                let allow = Header::from_bytes(&b"Allow"[..], &b"GET, HEAD"[..]).unwrap();
+                return Some(Response::empty(405).with_header(allow).boxed());
+            }
+        }
+
+        let file_path = match self.resolve(relative) {
+            Some(file_path) => file_path,
+            None => return Some(Response::empty(400).boxed()),
+        };
+
+        let metadata = match fs::metadata(&file_path) {
+            Ok(metadata) => metadata,
+            Err(err) => return Some(error_response(&err)),
+        };
+
+        let (file_path, metadata) = if metadata.is_dir() {
+            if !relative.ends_with('/') {
+                let location = format!("{}/{}", path, query);
+                let location = Header::from_bytes(&b"Location"[..], location.as_bytes()).unwrap();
+                return Some(Response::empty(301).with_header(location).boxed());
+            }
+
+            let index = self.index_files.iter().find_map(|name| {
+                let file_path = file_path.join(name);
+                match fs::metadata(&file_path) {
+                    Ok(metadata) if metadata.is_file() => Some((file_path, metadata)),
+                    _ => None,
+                }
+            });
+            match index {
+                Some(index) => index,
+                None => return Some(Response::empty(404).boxed()),
+            }
+        } else {
+            (file_path, metadata)
+        };
+
+        let modified = metadata.modified().ok();
+        let etag = entity_tag(&metadata, modified);
+        let mut validators = vec![Header::from_bytes(&b"ETag"[..], etag.as_bytes()).unwrap()];
+        if let Some(modified) = modified {
+            let date = HttpDate::from(modified).to_string();
+            validators.push(Header::from_bytes(&b"Last-Modified"[..], date.as_bytes()).unwrap());
+        }
+
+        if is_not_modified(request, &etag, modified) {
+            let mut response = Response::empty(304);
+            for header in validators {
+                response.add_header(header);
+            }
+            return Some(response.boxed());
+        }
+
+        let file = match File::open(&file_path) {
+            Ok(file) => file,
+            Err(err) => return Some(error_response(&err)),
+        };
+
+        let content_type = content_type(&file_path);
+        let mut response = Response::from_file(file).with_header(
+            Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes()).unwrap(),
+        );
+        for header in validators {
+            response.add_header(header);
+        }
+        Some(response.boxed())
+    }
+
+    /// Returns the part of the path following the prefix, which is empty or starts with a
+    /// slash.
+    fn strip_prefix<'a>(&self, path: &'a str) -> Option<&'a str> {
+        let relative = path.strip_prefix(self.prefix.as_str())?;
+        if relative.is_empty() || relative.starts_with('/') {
+            Some(relative)
+        } else {
+            None
+        }
+    }
+
+    /// Turns the path following the prefix into the path of a file inside the root, or returns
+    /// `None` if it is invalid.
+    fn resolve(&self, relative: &str) -> Option<PathBuf> {
+        let decoded = String::from_utf8(percent_decode(relative)?).ok()?;
+
+        let mut file_path = self.root.clone();
+        let mut depth = 0;
+        for segment in decoded.split('/') {
+            match segment {
+                "" | "." => (),
+                ".." => {
+                    if depth > 0 {
+                        file_path.pop();
+                        depth -= 1;
+                    }
+                }
+                _ => {
+                    // rejecting what the OS could interpret as more than a file name, such as
+                    // `a\..\..` or `C:` on Windows
+                    let mut components = Path::new(segment).components();
+                    match (components.next(), components.next()) {
+                        (Some(Component::Normal(_)), None)
+                            if !segment.contains('\\') && !segment.contains('\0') => {}
+                        _ => return None,
+                    }
+                    file_path.push(segment);
+                    depth += 1;
+                }
+            }
+        }
+
+        Some(file_path)
+    }
+}
+
+/// Returns the media type of a file, based on its extension, or `application/octet-stream`
+/// for unknown extensions.
+pub fn content_type(path: &Path) -> &'static str {
+    let extension = match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => extension.to_ascii_lowercase(),
+        None => return "application/octet-stream",
+    };
+
+    match extension.as_str() {
+        "css" => "text/css; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "htm" | "html" => "text/html; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "txt" => "text/plain; charset=utf-8",
+        "xml" => "text/xml; charset=utf-8",
+
+        "json" => "application/json",
+        "map" => "application/json",
+        "webmanifest" => "application/manifest+json",
+        "pdf" => "application/pdf",
+        "wasm" => "application/wasm",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "zip" => "application/zip",
+
+        "avif" => "image/avif",
+        "bmp" => "image/bmp",
+        "gif" => "image/gif",
+        "ico" => "image/x-icon",
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+
+        "otf" => "font/otf",
+        "ttf" => "font/ttf",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+
+        "flac" => "audio/flac",
+        "mp3" => "audio/mpeg",
+        "oga" | "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "weba" => "audio/webm",
+
+        "mp4" => "video/mp4",
+        "mpeg" => "video/mpeg",
+        "ogv" => "video/ogg",
+        "webm" => "video/webm",
+
+        _ => "application/octet-stream",
+    }
+}
+
+/// Decodes the `%XX` sequences of a URL path, or returns `None` if one of them is invalid.
+fn percent_decode(input: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len());
+    let mut bytes = input.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let high = (bytes.next()? as char).to_digit(16)?;
+            let low = (bytes.next()? as char).to_digit(16)?;
+            output.push((high * 16 + low) as u8);
+        } else {
+            output.push(byte);
+        }
+    }
+    Some(output)
+}
+
+/// Builds an entity tag that changes along with the modification time or the size of a file.
+fn entity_tag(metadata: &Metadata, modified: Option<SystemTime>) -> String {
+    let modified = modified
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since_epoch| since_epoch.as_nanos());
+    format!("\"{:x}-{:x}\"", modified, metadata.len())
+}
+
+/// Returns true if the request is conditional and the client already has the current version
+/// of the file.
+fn is_not_modified(request: &Request, etag: &str, modified: Option<SystemTime>) -> bool {
+    let header = |name: &'static str| {
+        request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv(name))
+            .map(|h| h.value.as_str().trim())
+    };
+
+    // `If-None-Match` takes precedence, using the weak comparison
+    if let Some(tags) = header("If-None-Match") {
+        return tags == "*"
+            || tags
+                .split(',')
+                .any(|tag| tag.trim().trim_start_matches("W/") == etag);
+    }
+
+    if let (Some(since), Some(modified)) = (header("If-Modified-Since"), modified) {
+        if let Ok(since) = HttpDate::from_str(since) {
+            return HttpDate::from(modified) <= since;
+        }
+    }
+
+    false
+}
+
+fn error_response(err: &IoError) -> ResponseBox {
+    let status_code = match err.kind() {
+        ErrorKind::NotFound => 404,
+        ErrorKind::PermissionDenied => 403,
+        _ => 500,
+    };
+    Response::empty(status_code).boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{percent_decode, StaticFiles};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("/a%20b%2Fc").unwrap(), b"/a b/c");
+        assert!(percent_decode("/a%2").is_none());
+        assert!(percent_decode("/a%zz").is_none());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let files = StaticFiles::new("/static/", "/srv/www");
+        let resolve = |relative| files.resolve(relative);
+
+        assert_eq!(resolve("/a/b.txt"), Some(PathBuf::from("/srv/www/a/b.txt")));
+        assert_eq!(resolve("/a/./b/../c"), Some(PathBuf::from("/srv/www/a/c")));
+        assert_eq!(resolve(""), Some(PathBuf::from("/srv/www")));
+
+        // never going above the root
+        assert_eq!(
+            resolve("/../../etc/passwd"),
+            Some(PathBuf::from("/srv/www/etc/passwd"))
+        );
+        assert_eq!(
+            resolve("/%2e%2e/%2E%2E/etc"),
+            Some(PathBuf::from("/srv/www/etc"))
+        );
+        assert_eq!(
+            resolve("/a/..%2f..%2fetc"),
+            Some(PathBuf::from("/srv/www/etc"))
+        );
+        assert_eq!(resolve("/a%5c..%5c..%5cetc"), None);
+        assert_eq!(resolve("/a%00"), None);
+        assert_eq!(resolve("/%ff"), None);
+    }
+
+    #[test]
+    fn test_prefix() {
+        let files = StaticFiles::new("/static", "/srv/www");
+        assert_eq!(files.strip_prefix("/static"), Some(""));
+        assert_eq!(files.strip_prefix("/static/a"), Some("/a"));
+        assert_eq!(files.strip_prefix("/staticfiles"), None);
+        assert_eq!(files.strip_prefix("/other"), None);
+
+        let files = StaticFiles::new("/", "/srv/www");
+        assert_eq!(files.strip_prefix("/a"), Some("/a"));
+    }
+}
@@ -0,0 +1,105 @@
+//! Live counters describing what the server is currently doing.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A snapshot of the activity of a [`Server`](crate::Server).
+///
+/// Obtained with [`Server::stats`](crate::Server::stats). The values are gathered without
+/// stopping the server, so they may already be outdated when you read them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServerStats {
+    /// Number of client connections currently open.
+    pub connections: usize,
+
+    /// Number of requests that have been returned by `recv()` and friends, but that have not
+    /// been answered yet.
+    pub requests_in_flight: usize,
+
+    /// Number of requests that have been read from a client, but that have not been returned
+    /// by `recv()` and friends yet.
+    pub requests_queued: usize,
+}
+
+/// Counters shared between the server, its accept thread and the client connections.
+#[derive(Default)]
+pub(crate) struct Counters {
+    connections: AtomicUsize,
+    requests_in_flight: AtomicUsize,
+    requests_queued: AtomicUsize,
+}
+
+impl Counters {
+    pub(crate) fn snapshot(&self) -> ServerStats {
+        ServerStats {
+            connections: self.connections.load(Ordering::Acquire),
+            requests_in_flight: self.requests_in_flight.load(Ordering::Acquire),
+            requests_queued: self.requests_queued.load(Ordering::Acquire),
+        }
+    }
+
+    /// Registers a new client connection, until the returned object is destroyed.
+    pub(crate) fn connection(self: &Arc<Self>) -> Registration {
+        Registration::new(self, |c| &c.connections)
+    }
+
+    /// Registers a request waiting in the messages queue, until the returned object is destroyed.
+    pub(crate) fn queued_request(self: &Arc<Self>) -> Registration {
+        Registration::new(self, |c| &c.requests_queued)
+    }
+
+    /// Registers a request being handled by the user, until the returned object is destroyed.
+    pub(crate) fn request_in_flight(self: &Arc<Self>) -> Registration {
+        Registration::new(self, |c| &c.requests_in_flight)
+    }
+}
+
+/// Increments one of the counters on creation and decrements it on destruction.
+pub(crate) struct Registration {
+    counters: Arc<Counters>,
+    counter: fn(&Counters) -> &AtomicUsize,
+}
+
+impl Registration {
+    fn new(counters: &Arc<Counters>, counter: fn(&Counters) -> &AtomicUsize) -> Registration {
+        counter(counters).fetch_add(1, Ordering::Release);
+        Registration {
+            counters: counters.clone(),
+            counter,
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        (self.counter)(&self.counters).fetch_sub(1, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Counters, ServerStats};
+    use std::sync::Arc;
+
+    #[test]
+    fn registrations_are_counted() {
+        let counters = Arc::new(Counters::default());
+
+        let connection = counters.connection();
+        let queued = counters.queued_request();
+        let in_flight = counters.request_in_flight();
+        let other_in_flight = counters.request_in_flight();
+
+        assert_eq!(
+            counters.snapshot(),
+            ServerStats {
+                connections: 1,
+                requests_in_flight: 2,
+                requests_queued: 1,
+            }
+        );
+
+        drop((connection, queued, in_flight, other_in_flight));
+        assert_eq!(counters.snapshot(), ServerStats::default());
+    }
+}
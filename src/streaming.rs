@@ -0,0 +1,111 @@
+use crate::util::ChunkedWriter;
+use crate::Header;
+use std::io::{Result as IoResult, Write};
+
+/// The body of a response written piece by piece. Obtained with
+/// [`Request::into_streaming_response`](crate::Request::into_streaming_response).
+///
+/// The data written is sent to the client as one chunk each time the writer is flushed, or
+/// once 32 kiB are pending. The body ends when `finish()` is called or when the
+/// `StreamingResponse` is destroyed.
+///
+/// Clients using HTTP 1.0 don't support the chunked transfer encoding: the data is written as
+/// is, the end of the body is marked by closing the connection and the trailers are dropped.
+///
+/// ```no_run
+/// use std::io::Write;
+///
+/// # let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+/// let request = server.recv().unwrap();
+/// let mut response = request.into_streaming_response(200, vec![]).unwrap();
+///
+/// for step in 1..=10 {
+///     writeln!(response, "step {}/10", step).unwrap();
+///     response.flush().unwrap();
+///     // ...
+/// }
+///
+/// response.add_trailer("Server-Timing: total;dur=1234".parse().unwrap());
+/// response.finish().unwrap();
+/// ```
+pub struct StreamingResponse {
+    body: Body,
+    trailers: Vec<Header>,
+    finished: bool,
+}
+
+enum Body {
+    Chunked(ChunkedWriter<Box<dyn Write + Send + 'static>>),
+    // written as is, when the client uses HTTP 1.0 or when the response has no body
+    Raw(Box<dyn Write + Send + 'static>),
+}
+
+impl StreamingResponse {
+    pub(crate) fn new(writer: Box<dyn Write + Send + 'static>, chunked: bool) -> Self {
+        let body = if chunked {
+            Body::Chunked(ChunkedWriter::new(writer))
+        } else {
+            Body::Raw(writer)
+        };
+
+        StreamingResponse {
+            body,
+            trailers: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Adds a header sent after the body, for instance a checksum of the data.
+    ///
+    /// Clients that didn't send a `TE: trailers` header may ignore the trailers, and those using
+    /// HTTP 1.0 never receive them. Their names can be announced with a `Trailer` header passed
+    /// to `into_streaming_response`, which is removed when the trailers can't be sent.
+    ///
+    /// The fields that can't be sent after the body, such as `Content-Length`, `Content-Type`
+    /// or `Set-Cookie`, are dropped (see RFC 9110 section 6.5.1).
+    pub fn add_trailer(&mut self, trailer: Header) {
+        self.trailers.push(trailer);
+    }
+
+    /// Ends the body, sending the pending data and the trailers.
+    ///
+    /// This is done automatically when the `StreamingResponse` is destroyed, but any error is
+    /// ignored then.
+    pub fn finish(mut self) -> IoResult<()> {
+        self.finish_impl()
+    }
+
+    fn finish_impl(&mut self) -> IoResult<()> {
+        if self.finished {
+            return Ok(());
+        }
+
+        self.finished = true;
+        match self.body {
+            Body::Chunked(ref mut writer) => writer.finish(&self.trailers),
+            Body::Raw(ref mut writer) => writer.flush(),
+        }
+    }
+}
+
+impl Write for StreamingResponse {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        match self.body {
+            Body::Chunked(ref mut writer) => writer.write(buf),
+            Body::Raw(ref mut writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        match self.body {
+            Body::Chunked(ref mut writer) => writer.flush(),
+            Body::Raw(ref mut writer) => writer.flush(),
+        }
+    }
+}
+
+impl Drop for StreamingResponse {
+    fn drop(&mut self) {
+        let _ = self.finish_impl(); // ignoring any potential error
+    }
+}
@@ -0,0 +1,173 @@
+use crate::Header;
+use std::io::{Result as IoResult, Write};
+
+/// Largest chunk sent before the writer is flushed.
+const MAX_CHUNK_SIZE: usize = 32 * 1024;
+
+/// Fields that can't be sent as trailers, because they are needed to frame, route,
+/// authenticate or process the message before its body, see RFC 9110 section 6.5.1.
+const FORBIDDEN_TRAILERS: &[&str] = &[
+    "Age",
+    "Authorization",
+    "Cache-Control",
+    "Connection",
+    "Content-Encoding",
+    "Content-Length",
+    "Content-Range",
+    "Content-Type",
+    "Date",
+    "Expect",
+    "Expires",
+    "Host",
+    "Keep-Alive",
+    "Location",
+    "Max-Forwards",
+    "Pragma",
+    "Proxy-Authenticate",
+    "Proxy-Authorization",
+    "Range",
+    "Retry-After",
+    "Set-Cookie",
+    "TE",
+    "Trailer",
+    "Transfer-Encoding",
+    "Upgrade",
+    "Vary",
+    "WWW-Authenticate",
+];
+
+/// Returns true if the header may be sent after the body.
+pub fn is_allowed_trailer(trailer: &Header) -> bool {
+    let field = trailer.field.as_str().as_str();
+    let conditional = field.len() > 3 && field[..3].eq_ignore_ascii_case("If-");
+    !conditional && !FORBIDDEN_TRAILERS.iter().any(|&f| trailer.field.equiv(f))
+}
+
+/// Writes a body with the chunked transfer encoding.
+///
+/// Unlike `chunked_transfer::Encoder`, flushing this writer sends the data written so far as
+/// one chunk and flushes the underlying writer, so that the client receives it right away.
+/// The final empty chunk, followed by the trailers if any, is sent by `finish()`, or when the
+/// writer is destroyed.
+pub struct ChunkedWriter<W: Write> {
+    inner: W,
+    buffer: Vec<u8>,
+    finished: bool,
+}
+
+impl<W: Write> ChunkedWriter<W> {
+    pub fn new(inner: W) -> ChunkedWriter<W> {
+        ChunkedWriter {
+            inner,
+            buffer: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Sends the pending data, the final chunk and the trailers, which ends the body.
+    ///
+    /// The trailers whose field isn't allowed after the body are dropped.
+    pub fn finish(&mut self, trailers: &[Header]) -> IoResult<()> {
+        self.finished = true;
+        self.send_chunk()?;
+        self.inner.write_all(b"0\r\n")?;
+        for trailer in trailers.iter().filter(|t| is_allowed_trailer(t)) {
+            write!(self.inner, "{}: {}\r\n", trailer.field, trailer.value)?;
+        }
+        self.inner.write_all(b"\r\n")?;
+        self.inner.flush()
+    }
+
+    /// Sends the pending data as a chunk, without flushing the underlying writer.
+    fn send_chunk(&mut self) -> IoResult<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        write!(self.inner, "{:x}\r\n", self.buffer.len())?;
+        self.inner.write_all(&self.buffer)?;
+        self.inner.write_all(b"\r\n")?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ChunkedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= MAX_CHUNK_SIZE {
+            self.send_chunk()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.send_chunk()?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for ChunkedWriter<W> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.finish(&[]); // ignoring any potential error
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChunkedWriter;
+    use std::io::Write;
+
+    #[test]
+    fn test_chunk_per_flush() {
+        let mut output = Vec::new();
+        {
+            let mut writer = ChunkedWriter::new(&mut output);
+            writer.write_all(b"hello ").unwrap();
+            writer.write_all(b"world").unwrap();
+            writer.flush().unwrap();
+            // flushing again doesn't send an empty chunk, which would end the body
+            writer.flush().unwrap();
+            writer.write_all(b"!").unwrap();
+        }
+        assert_eq!(output, b"b\r\nhello world\r\n1\r\n!\r\n0\r\n\r\n");
+    }
+
+    #[test]
+    fn test_trailers() {
+        let mut output = Vec::new();
+        let mut writer = ChunkedWriter::new(&mut output);
+        writer.write_all(b"hello").unwrap();
+        writer.finish(&["Checksum: 1234".parse().unwrap()]).unwrap();
+        drop(writer);
+        assert_eq!(output, b"5\r\nhello\r\n0\r\nChecksum: 1234\r\n\r\n");
+    }
+
+    #[test]
+    fn test_forbidden_trailers() {
+        let mut output = Vec::new();
+        let mut writer = ChunkedWriter::new(&mut output);
+        writer
+            .finish(&[
+                "Content-Length: 5".parse().unwrap(),
+                "transfer-encoding: gzip".parse().unwrap(),
+                "If-Match: *".parse().unwrap(),
+                "Checksum: 1234".parse().unwrap(),
+            ])
+            .unwrap();
+        drop(writer);
+        assert_eq!(output, b"0\r\nChecksum: 1234\r\n\r\n");
+    }
+
+    #[test]
+    fn test_large_write() {
+        let mut output = Vec::new();
+        ChunkedWriter::new(&mut output)
+            .write_all(&[b'a'; 40 * 1024])
+            .unwrap();
+        assert!(output.starts_with(b"a000\r\naaaa"));
+        assert!(output.ends_with(b"aaaa\r\n0\r\n\r\n"));
+    }
+}
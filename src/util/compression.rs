@@ -0,0 +1,175 @@
+use brotli::{CompressorReader, Decompressor};
+use flate2::read::{GzDecoder, GzEncoder, ZlibDecoder, ZlibEncoder};
+use flate2::Compression;
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult};
+
+/// Size of the buffers used by the brotli compressor and decompressor.
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+/// Brotli quality, from 0 to 11. The highest ones are much too slow to compress on the fly.
+const BROTLI_QUALITY: u32 = 5;
+
+/// Base 2 logarithm of the brotli window size.
+const BROTLI_WINDOW_BITS: u32 = 22;
+
+/// Content codings that the library can produce and decode.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ContentCoding {
+    /// Brotli (RFC 7932).
+    Brotli,
+    /// DEFLATE data in the gzip format (RFC 1952).
+    Gzip,
+    /// DEFLATE data in the zlib format (RFC 1950), which is what `deflate` means in HTTP.
+    Deflate,
+}
+
+impl ContentCoding {
+    /// Returns the name used in the `Accept-Encoding` and `Content-Encoding` headers.
+    pub fn name(self) -> &'static str {
+        match self {
+            ContentCoding::Brotli => "br",
+            ContentCoding::Gzip => "gzip",
+            ContentCoding::Deflate => "deflate",
+        }
+    }
+
+    /// Parses a coding of a `Content-Encoding` header, case-insensitively.
+    pub fn from_name(name: &str) -> Option<ContentCoding> {
+        if name.eq_ignore_ascii_case("br") {
+            Some(ContentCoding::Brotli)
+        } else if name.eq_ignore_ascii_case("gzip") || name.eq_ignore_ascii_case("x-gzip") {
+            Some(ContentCoding::Gzip)
+        } else if name.eq_ignore_ascii_case("deflate") {
+            Some(ContentCoding::Deflate)
+        } else {
+            None
+        }
+    }
+}
+
+/// Reads the data of another reader, compressed with the given coding.
+pub enum CompressingReader<R: Read> {
+    Brotli(Box<CompressorReader<R>>),
+    Gzip(GzEncoder<R>),
+    Deflate(ZlibEncoder<R>),
+}
+
+impl<R: Read> CompressingReader<R> {
+    pub fn new(inner: R, coding: ContentCoding) -> CompressingReader<R> {
+        match coding {
+            ContentCoding::Brotli => CompressingReader::Brotli(Box::new(CompressorReader::new(
+                inner,
+                BROTLI_BUFFER_SIZE,
+                BROTLI_QUALITY,
+                BROTLI_WINDOW_BITS,
+            ))),
+            ContentCoding::Gzip => {
+                CompressingReader::Gzip(GzEncoder::new(inner, Compression::fast()))
+            }
+            ContentCoding::Deflate => {
+                CompressingReader::Deflate(ZlibEncoder::new(inner, Compression::fast()))
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for CompressingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        match self {
+            CompressingReader::Brotli(reader) => reader.read(buf),
+            CompressingReader::Gzip(reader) => reader.read(buf),
+            CompressingReader::Deflate(reader) => reader.read(buf),
+        }
+    }
+}
+
+/// Reads the data of another reader, decompressed according to the given coding.
+///
+/// Reading fails if the data is corrupted or truncated.
+pub enum DecompressingReader<R: Read> {
+    Brotli(Box<Decompressor<R>>),
+    Gzip(GzDecoder<R>),
+    Deflate(ZlibDecoder<R>),
+}
+
+impl<R: Read> DecompressingReader<R> {
+    pub fn new(inner: R, coding: ContentCoding) -> DecompressingReader<R> {
+        match coding {
+            ContentCoding::Brotli => {
+                DecompressingReader::Brotli(Box::new(Decompressor::new(inner, BROTLI_BUFFER_SIZE)))
+            }
+            ContentCoding::Gzip => DecompressingReader::Gzip(GzDecoder::new(inner)),
+            ContentCoding::Deflate => DecompressingReader::Deflate(ZlibDecoder::new(inner)),
+        }
+    }
+}
+
+impl<R: Read> Read for DecompressingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        match self {
+            DecompressingReader::Brotli(reader) => reader.read(buf),
+            DecompressingReader::Gzip(reader) => reader.read(buf),
+            DecompressingReader::Deflate(reader) => reader.read(buf),
+        }
+    }
+}
+
+/// A reader that fails right away, for data whose coding isn't supported.
+pub struct UnsupportedCoding;
+
+impl Read for UnsupportedCoding {
+    fn read(&mut self, _: &mut [u8]) -> IoResult<usize> {
+        Err(IoError::new(
+            ErrorKind::Unsupported,
+            "Unsupported content encoding",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CompressingReader, ContentCoding, DecompressingReader};
+    use std::io::Read;
+
+    fn roundtrip(coding: ContentCoding) {
+        let input = b"hello world ".repeat(10000);
+        let compressed = CompressingReader::new(&input[..], coding);
+        let mut output = Vec::new();
+        DecompressingReader::new(compressed, coding)
+            .read_to_end(&mut output)
+            .unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_roundtrips() {
+        roundtrip(ContentCoding::Brotli);
+        roundtrip(ContentCoding::Gzip);
+        roundtrip(ContentCoding::Deflate);
+    }
+
+    #[test]
+    fn test_corrupted_data() {
+        let mut input = Vec::new();
+        CompressingReader::new(&b"hello world"[..], ContentCoding::Deflate)
+            .read_to_end(&mut input)
+            .unwrap();
+        // breaking the checksum
+        *input.last_mut().unwrap() ^= 1;
+
+        let mut output = Vec::new();
+        assert!(DecompressingReader::new(&input[..], ContentCoding::Deflate)
+            .read_to_end(&mut output)
+            .is_err());
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(ContentCoding::from_name("BR"), Some(ContentCoding::Brotli));
+        assert_eq!(
+            ContentCoding::from_name("x-gzip"),
+            Some(ContentCoding::Gzip)
+        );
+        assert_eq!(ContentCoding::from_name("compress"), None);
+    }
+}
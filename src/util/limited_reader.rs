@@ -0,0 +1,153 @@
+use std::io::Result as IoResult;
+use std::io::{Error as IoError, ErrorKind, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A `Reader` that returns an error if the sub-reader yields more than a given number of bytes.
+///
+/// Unlike `std::io::Take`, which silently stops at the limit, this makes it possible to tell
+/// a body that is exactly as large as the limit apart from a body that is too large.
+pub struct LimitedReader<R>
+where
+    R: Read,
+{
+    reader: R,
+    remaining: usize,
+    exceeded: bool,
+    // set to true once the limit has been exceeded
+    notify: Option<Arc<AtomicBool>>,
+}
+
+impl<R> LimitedReader<R>
+where
+    R: Read,
+{
+    pub fn new(reader: R, limit: usize) -> LimitedReader<R> {
+        LimitedReader {
+            reader,
+            remaining: limit,
+            exceeded: false,
+            notify: None,
+        }
+    }
+
+    /// Builds a reader that fails right away, without reading anything from `reader`.
+    ///
+    /// This is used when the size of the data is known in advance to be over the limit.
+    pub fn exceeded(reader: R) -> LimitedReader<R> {
+        LimitedReader {
+            reader,
+            remaining: 0,
+            exceeded: true,
+            notify: None,
+        }
+    }
+
+    /// Sets `flag` to true once the limit has been exceeded.
+    pub fn with_notify(mut self, flag: Arc<AtomicBool>) -> LimitedReader<R> {
+        if self.exceeded {
+            flag.store(true, Ordering::Release);
+        }
+        self.notify = Some(flag);
+        self
+    }
+
+    fn set_exceeded(&mut self) {
+        self.exceeded = true;
+        if let Some(ref flag) = self.notify {
+            flag.store(true, Ordering::Release);
+        }
+    }
+}
+
+fn limit_exceeded() -> IoError {
+    IoError::new(
+        ErrorKind::InvalidData,
+        "Request body exceeds the size limit",
+    )
+}
+
+impl<R> Read for LimitedReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if self.exceeded {
+            return Err(limit_exceeded());
+        }
+
+        // reading one byte past the limit tells us whether the data goes over it
+        let max = buf.len().min(self.remaining.saturating_add(1));
+        let len = self.reader.read(&mut buf[..max])?;
+
+        if len > self.remaining {
+            // the bytes within the limit are still returned, the error comes with the next read
+            self.set_exceeded();
+            let len = self.remaining;
+            self.remaining = 0;
+            return if len == 0 {
+                Err(limit_exceeded())
+            } else {
+                Ok(len)
+            };
+        }
+
+        self.remaining -= len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LimitedReader;
+    use std::io::{ErrorKind, Read};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_within_limit() {
+        let mut reader = LimitedReader::new(&b"hello"[..], 5);
+
+        let mut string = String::new();
+        reader.read_to_string(&mut string).unwrap();
+        assert_eq!(string, "hello");
+    }
+
+    #[test]
+    fn test_over_limit() {
+        let mut reader = LimitedReader::new(&b"hello world"[..], 5);
+
+        let mut buf = Vec::new();
+        let err = reader.read_to_end(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(buf, b"hello");
+
+        // the reader keeps failing
+        assert!(reader.read(&mut [0; 16]).is_err());
+    }
+
+    #[test]
+    fn test_exceeded() {
+        let mut source = &b"hello"[..];
+
+        {
+            let mut reader = LimitedReader::exceeded(&mut source);
+            assert!(reader.read(&mut [0; 16]).is_err());
+        }
+
+        assert_eq!(source, b"hello");
+    }
+
+    #[test]
+    fn test_notify() {
+        let flag = Arc::new(AtomicBool::new(false));
+        let mut reader = LimitedReader::new(&b"hello world"[..], 5).with_notify(flag.clone());
+
+        let mut buf = [0; 5];
+        reader.read_exact(&mut buf).unwrap();
+        assert!(!flag.load(Ordering::Acquire));
+
+        assert!(reader.read(&mut buf).is_err());
+        assert!(flag.load(Ordering::Acquire));
+    }
+}
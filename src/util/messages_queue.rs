@@ -1,4 +1,5 @@
 use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Condvar, Mutex};
 use std::time::{Duration, Instant};
 
@@ -13,26 +14,89 @@ where
 {
     queue: Mutex<VecDeque<Control<T>>>,
     condvar: Condvar,
+
+    // notified whenever an element is popped
+    not_full: Condvar,
+
+    // maximum number of elements for `push_wait` and `try_push`, `None` if unlimited
+    max_len: Option<usize>,
+
+    // set by `unbound()`
+    unbound: AtomicBool,
 }
 
 impl<T> MessagesQueue<T>
 where
     T: Send,
 {
-    pub fn with_capacity(capacity: usize) -> Arc<MessagesQueue<T>> {
+    /// Builds a queue. `max_len` only applies to `push_wait` and `try_push`.
+    pub fn new(max_len: Option<usize>) -> Arc<MessagesQueue<T>> {
         Arc::new(MessagesQueue {
-            queue: Mutex::new(VecDeque::with_capacity(capacity)),
+            queue: Mutex::new(VecDeque::with_capacity(8)),
             condvar: Condvar::new(),
+            not_full: Condvar::new(),
+            max_len,
+            unbound: AtomicBool::new(false),
         })
     }
 
-    /// Pushes an element to the queue.
+    /// Pushes an element to the queue, even if it is full.
     pub fn push(&self, value: T) {
         let mut queue = self.queue.lock().unwrap();
         queue.push_back(Control::Elem(value));
         self.condvar.notify_one();
     }
 
+    /// Pushes an element to the queue. Blocks while the queue is full.
+    pub fn push_wait(&self, value: T) {
+        let mut queue = self.queue.lock().unwrap();
+        while self.is_full(&queue) {
+            queue = self.not_full.wait(queue).unwrap();
+        }
+        queue.push_back(Control::Elem(value));
+        self.condvar.notify_one();
+    }
+
+    /// Pushes an element to the queue, or gives it back if the queue is full.
+    pub fn try_push(&self, value: T) -> Result<(), T> {
+        let mut queue = self.queue.lock().unwrap();
+        if self.is_full(&queue) {
+            return Err(value);
+        }
+        queue.push_back(Control::Elem(value));
+        self.condvar.notify_one();
+        Ok(())
+    }
+
+    /// Removes the limit on the number of elements, and wakes up the threads blocked in
+    /// `push_wait`.
+    ///
+    /// This is used when the elements won't be popped anymore.
+    pub fn unbound(&self) {
+        let _queue = self.queue.lock().unwrap();
+        self.unbound.store(true, Ordering::Release);
+        self.not_full.notify_all();
+    }
+
+    /// Returns the number of elements in the queue.
+    pub fn len(&self) -> usize {
+        Self::count(&self.queue.lock().unwrap())
+    }
+
+    fn count(queue: &VecDeque<Control<T>>) -> usize {
+        queue
+            .iter()
+            .filter(|c| matches!(c, Control::Elem(_)))
+            .count()
+    }
+
+    fn is_full(&self, queue: &VecDeque<Control<T>>) -> bool {
+        match self.max_len {
+            Some(max_len) => !self.unbound.load(Ordering::Acquire) && Self::count(queue) >= max_len,
+            None => false,
+        }
+    }
+
     /// Unblock one thread stuck in pop loop.
     pub fn unblock(&self) {
         let mut queue = self.queue.lock().unwrap();
@@ -47,7 +111,10 @@ where
 
         loop {
             match queue.pop_front() {
-                Some(Control::Elem(value)) => return Some(value),
+                Some(Control::Elem(value)) => {
+                    self.not_full.notify_one();
+                    return Some(value);
+                }
                 Some(Control::Unblock) => return None,
                 None => (),
             }
@@ -60,7 +127,10 @@ where
     pub fn try_pop(&self) -> Option<T> {
         let mut queue = self.queue.lock().unwrap();
         match queue.pop_front() {
-            Some(Control::Elem(value)) => Some(value),
+            Some(Control::Elem(value)) => {
+                self.not_full.notify_one();
+                Some(value)
+            }
             Some(Control::Unblock) | None => None,
         }
     }
@@ -73,7 +143,10 @@ where
         let mut duration = timeout;
         loop {
             match queue.pop_front() {
-                Some(Control::Elem(value)) => return Some(value),
+                Some(Control::Elem(value)) => {
+                    self.not_full.notify_one();
+                    return Some(value);
+                }
                 Some(Control::Unblock) => return None,
                 None => (),
             }
@@ -1,19 +1,35 @@
+pub use self::chunked_writer::{is_allowed_trailer, ChunkedWriter};
+#[cfg(feature = "compression")]
+pub use self::compression::{
+    CompressingReader, ContentCoding, DecompressingReader, UnsupportedCoding,
+};
 pub use self::custom_stream::CustomStream;
 pub use self::equal_reader::EqualReader;
 pub use self::fused_reader::FusedReader;
+pub use self::limited_reader::LimitedReader;
 pub use self::messages_queue::MessagesQueue;
+pub use self::ranges::{parse_range_header, RangePart, RangesReader};
 pub use self::refined_tcp_stream::RefinedTcpStream;
+#[cfg(target_os = "linux")]
+pub use self::sendfile::sendfile;
+pub use self::sequential::SequentialWriterBuilder;
 pub use self::sequential::{SequentialReader, SequentialReaderBuilder};
-pub use self::sequential::{SequentialWriter, SequentialWriterBuilder};
 pub use self::task_pool::TaskPool;
 
 use std::str::FromStr;
 
+mod chunked_writer;
+#[cfg(feature = "compression")]
+mod compression;
 mod custom_stream;
 mod equal_reader;
 mod fused_reader;
+mod limited_reader;
 mod messages_queue;
+mod ranges;
 pub(crate) mod refined_tcp_stream;
+#[cfg(target_os = "linux")]
+mod sendfile;
 mod sequential;
 mod task_pool;
 
@@ -0,0 +1,215 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult, Seek, SeekFrom};
+
+/// Number of ranges above which a `Range` header is ignored, as it is then more likely to be
+/// an attempt to make the server do a lot of work than a legitimate request.
+const MAX_RANGES: usize = 64;
+
+/// Parses the value of a `Range` header for a body of `length` bytes.
+///
+/// Returns the satisfiable ranges, as inclusive bounds, sorted and with the overlapping or
+/// adjacent ones merged. The list is empty if none of them can be satisfied. Returns `None`
+/// if the header is invalid or uses another unit than bytes, in which case it must be ignored.
+pub fn parse_range_header(value: &str, length: u64) -> Option<Vec<(u64, u64)>> {
+    let (unit, specs) = value.trim().split_once('=')?;
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+
+    let mut ranges = Vec::new();
+    let mut count = 0;
+    for spec in specs.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        count += 1;
+        if count > MAX_RANGES {
+            return None;
+        }
+
+        let (first, last) = spec.split_once('-')?;
+        let (first, last) = (first.trim(), last.trim());
+
+        let range = if first.is_empty() {
+            // the last bytes
+            let suffix = parse_number(last)?;
+            if suffix == 0 || length == 0 {
+                continue;
+            }
+            (length.saturating_sub(suffix), length - 1)
+        } else {
+            let first = parse_number(first)?;
+            let last = if last.is_empty() {
+                u64::MAX
+            } else {
+                parse_number(last)?
+            };
+            if last < first {
+                return None;
+            }
+            if first >= length {
+                continue;
+            }
+            (first, last.min(length - 1))
+        };
+
+        ranges.push(range);
+    }
+
+    if count == 0 {
+        return None;
+    }
+
+    ranges.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (first, last) in ranges {
+        match merged.last_mut() {
+            Some(previous) if first <= previous.1.saturating_add(1) => {
+                previous.1 = previous.1.max(last);
+            }
+            _ => merged.push((first, last)),
+        }
+    }
+
+    Some(merged)
+}
+
+/// Parses a number made of digits only, unlike `u64::from_str` which also accepts a sign.
+fn parse_number(input: &str) -> Option<u64> {
+    if input.is_empty() || !input.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    input.parse().ok()
+}
+
+/// A part of the body of a range response.
+pub struct RangePart {
+    /// Sent before the data of the range, such as the headers of a part of a multipart body.
+    pub prefix: Vec<u8>,
+    pub start: u64,
+    pub length: u64,
+}
+
+/// Reads some ranges of a file, each one preceded by a prefix.
+///
+/// `file` must share its position with `reader`, as `File::try_clone` does. It is used to seek
+/// to the start of each range while the data is read from `reader`.
+pub struct RangesReader<R> {
+    reader: R,
+    file: File,
+    parts: VecDeque<RangePart>,
+    // number of bytes of the prefix of the first part already read
+    prefix_position: usize,
+    seeked: bool,
+}
+
+impl<R: Read> RangesReader<R> {
+    pub fn new(reader: R, file: File, parts: Vec<RangePart>) -> RangesReader<R> {
+        RangesReader {
+            reader,
+            file,
+            parts: parts.into(),
+            prefix_position: 0,
+            seeked: false,
+        }
+    }
+}
+
+impl<R: Read> Read for RangesReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        while let Some(part) = self.parts.front_mut() {
+            if self.prefix_position < part.prefix.len() {
+                let prefix = &part.prefix[self.prefix_position..];
+                let len = buf.len().min(prefix.len());
+                buf[..len].copy_from_slice(&prefix[..len]);
+                self.prefix_position += len;
+                return Ok(len);
+            }
+
+            if part.length > 0 {
+                if !self.seeked {
+                    self.file.seek(SeekFrom::Start(part.start))?;
+                    self.seeked = true;
+                }
+
+                let max = (buf.len() as u64).min(part.length) as usize;
+                let len = self.reader.read(&mut buf[..max])?;
+                if len == 0 && max > 0 {
+                    return Err(IoError::new(
+                        ErrorKind::UnexpectedEof,
+                        "The file is shorter than expected",
+                    ));
+                }
+                part.length -= len as u64;
+                return Ok(len);
+            }
+
+            self.parts.pop_front();
+            self.prefix_position = 0;
+            self.seeked = false;
+        }
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_range_header;
+
+    #[test]
+    fn test_single_ranges() {
+        assert_eq!(parse_range_header("bytes=0-99", 1000), Some(vec![(0, 99)]));
+        assert_eq!(
+            parse_range_header("bytes=500-", 1000),
+            Some(vec![(500, 999)])
+        );
+        assert_eq!(
+            parse_range_header("bytes=-100", 1000),
+            Some(vec![(900, 999)])
+        );
+        assert_eq!(
+            parse_range_header("bytes=-2000", 1000),
+            Some(vec![(0, 999)])
+        );
+        assert_eq!(
+            parse_range_header("bytes=900-2000", 1000),
+            Some(vec![(900, 999)])
+        );
+        assert_eq!(parse_range_header("Bytes = 1-1", 1000), Some(vec![(1, 1)]));
+    }
+
+    #[test]
+    fn test_multiple_ranges() {
+        assert_eq!(
+            parse_range_header("bytes=500-599, 0-99", 1000),
+            Some(vec![(0, 99), (500, 599)])
+        );
+        // overlapping and adjacent ranges are merged
+        assert_eq!(
+            parse_range_header("bytes=0-99,50-150,151-200,-10", 1000),
+            Some(vec![(0, 200), (990, 999)])
+        );
+    }
+
+    #[test]
+    fn test_unsatisfiable_ranges() {
+        assert_eq!(parse_range_header("bytes=1000-", 1000), Some(vec![]));
+        assert_eq!(parse_range_header("bytes=-0", 1000), Some(vec![]));
+        assert_eq!(parse_range_header("bytes=0-", 0), Some(vec![]));
+        assert_eq!(
+            parse_range_header("bytes=2000-3000, 10-19", 1000),
+            Some(vec![(10, 19)])
+        );
+    }
+
+    #[test]
+    fn test_invalid_ranges() {
+        assert_eq!(parse_range_header("items=0-1", 1000), None);
+        assert_eq!(parse_range_header("bytes=", 1000), None);
+        assert_eq!(parse_range_header("bytes=5-1", 1000), None);
+        assert_eq!(parse_range_header("bytes=a-b", 1000), None);
+        assert_eq!(parse_range_header("bytes=+1-2", 1000), None);
+        assert_eq!(parse_range_header("bytes=1", 1000), None);
+        let many = format!("bytes={}", vec!["0-0"; 100].join(","));
+        assert_eq!(parse_range_header(&many, 1000), None);
+    }
+}
@@ -1,6 +1,8 @@
 use std::io::Result as IoResult;
-use std::io::{Read, Write};
+use std::io::{Error as IoError, ErrorKind, Read, Write};
 use std::net::{Shutdown, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::connection::Connection;
 #[cfg(any(
@@ -76,6 +78,18 @@ impl Stream {
             Stream::Https(ssl_stream) => ssl_stream.shutdown(how),
         }
     }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> IoResult<()> {
+        match self {
+            Stream::Http(tcp_stream) => tcp_stream.set_read_timeout(timeout),
+            #[cfg(any(
+                feature = "ssl-openssl",
+                feature = "ssl-rustls",
+                feature = "ssl-native-tls"
+            ))]
+            Stream::Https(ssl_stream) => ssl_stream.set_read_timeout(timeout),
+        }
+    }
 }
 
 impl Read for Stream {
@@ -118,10 +132,124 @@ impl Write for Stream {
     }
 }
 
+/// Controls how long the reads of a `RefinedTcpStream` are allowed to block.
+///
+/// The object is shared between the stream and the code driving it, so that different timeouts
+/// can be used while waiting for a request, reading its header and reading its body.
+#[derive(Clone, Default)]
+pub(crate) struct ReadTimeout(Arc<Mutex<ReadTimeoutInner>>);
+
+#[derive(Default)]
+struct ReadTimeoutInner {
+    state: ReadTimeoutState,
+    // true until the first byte of the awaited request has been received
+    idle: bool,
+}
+
+#[derive(Clone, Copy)]
+enum ReadTimeoutState {
+    /// Reads may block forever.
+    Disabled,
+    /// Waiting for the first byte of a request. Reads may block for `idle`, then the rest of
+    /// the header must be received within `header`.
+    AwaitingRequest {
+        idle: Option<Duration>,
+        header: Option<Duration>,
+    },
+    /// Reads fail once this instant has been reached.
+    Deadline(Instant),
+    /// Each read may block for this duration.
+    Idle(Duration),
+}
+
+impl Default for ReadTimeoutState {
+    fn default() -> Self {
+        ReadTimeoutState::Disabled
+    }
+}
+
+impl ReadTimeout {
+    /// Waits at most `idle` for the next request to start, then at most `header` for its header.
+    pub(crate) fn await_request(&self, idle: Option<Duration>, header: Option<Duration>) {
+        let mut inner = self.0.lock().unwrap();
+        inner.state = ReadTimeoutState::AwaitingRequest { idle, header };
+        inner.idle = true;
+    }
+
+    /// Waits for the first request of a connection, which must be received entirely within
+    /// `header`.
+    pub(crate) fn await_first_request(&self, header: Option<Duration>) {
+        self.set_deadline(header);
+        self.0.lock().unwrap().idle = true;
+    }
+
+    /// Makes all reads fail after `timeout` has elapsed.
+    pub(crate) fn set_deadline(&self, timeout: Option<Duration>) {
+        self.0.lock().unwrap().state = match timeout {
+            Some(timeout) => ReadTimeoutState::Deadline(Instant::now() + timeout),
+            None => ReadTimeoutState::Disabled,
+        };
+    }
+
+    /// Makes each read fail if no data arrives within `timeout`.
+    pub(crate) fn set_idle(&self, timeout: Option<Duration>) {
+        self.0.lock().unwrap().state = match timeout {
+            Some(timeout) => ReadTimeoutState::Idle(timeout),
+            None => ReadTimeoutState::Disabled,
+        };
+    }
+
+    /// Returns true if no byte of the request passed to `await_request` has been received yet.
+    pub(crate) fn is_awaiting_request(&self) -> bool {
+        match self.0.lock().unwrap().state {
+            ReadTimeoutState::AwaitingRequest { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if no byte of the awaited request, first request included, has been
+    /// received yet.
+    pub(crate) fn is_idle(&self) -> bool {
+        self.0.lock().unwrap().idle
+    }
+
+    /// Returns the timeout to apply to the next read, or an error if the deadline has passed.
+    fn next_read(&self) -> IoResult<Option<Duration>> {
+        match self.0.lock().unwrap().state {
+            ReadTimeoutState::Disabled => Ok(None),
+            ReadTimeoutState::AwaitingRequest { idle, .. } => Ok(idle),
+            ReadTimeoutState::Idle(timeout) => Ok(Some(timeout)),
+            ReadTimeoutState::Deadline(deadline) => {
+                let now = Instant::now();
+                if deadline <= now {
+                    Err(IoError::new(ErrorKind::TimedOut, "Read timed out"))
+                } else {
+                    Ok(Some(deadline - now))
+                }
+            }
+        }
+    }
+
+    /// Starts the header deadline once the first byte of a request has been received.
+    fn data_received(&self) {
+        let mut inner = self.0.lock().unwrap();
+        inner.idle = false;
+        if let ReadTimeoutState::AwaitingRequest { header, .. } = inner.state {
+            inner.state = match header {
+                Some(header) => ReadTimeoutState::Deadline(Instant::now() + header),
+                None => ReadTimeoutState::Disabled,
+            };
+        }
+    }
+}
+
 pub struct RefinedTcpStream {
     stream: Stream,
     close_read: bool,
     close_write: bool,
+    read_timeout: ReadTimeout,
+    // the read timeout currently set on the socket
+    applied_read_timeout: Option<Duration>,
 }
 
 impl RefinedTcpStream {
@@ -132,17 +260,22 @@ impl RefinedTcpStream {
         let stream: Stream = stream.into();
 
         let (read, write) = (stream.clone(), stream);
+        let read_timeout = ReadTimeout::default();
 
         let read = RefinedTcpStream {
             stream: read,
             close_read: true,
             close_write: false,
+            read_timeout: read_timeout.clone(),
+            applied_read_timeout: None,
         };
 
         let write = RefinedTcpStream {
             stream: write,
             close_read: false,
             close_write: true,
+            read_timeout,
+            applied_read_timeout: None,
         };
 
         (read, write)
@@ -157,6 +290,22 @@ impl RefinedTcpStream {
     pub(crate) fn peer_addr(&mut self) -> IoResult<Option<SocketAddr>> {
         self.stream.peer_addr()
     }
+
+    /// Returns the object controlling the read timeouts of this stream.
+    pub(crate) fn read_timeout(&self) -> ReadTimeout {
+        self.read_timeout.clone()
+    }
+
+    /// Tells the client that no more data will be sent, while still allowing to read.
+    ///
+    /// Nothing is done for a connection that goes through SSL, as its socket must not be shut
+    /// down before the session has been closed.
+    pub(crate) fn shutdown_write(&mut self) -> IoResult<()> {
+        if self.secure() {
+            return Ok(());
+        }
+        self.stream.shutdown(Shutdown::Write)
+    }
 }
 
 impl Drop for RefinedTcpStream {
@@ -173,7 +322,25 @@ impl Drop for RefinedTcpStream {
 
 impl Read for RefinedTcpStream {
     fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
-        self.stream.read(buf)
+        let timeout = self.read_timeout.next_read()?;
+        if timeout != self.applied_read_timeout {
+            self.stream.set_read_timeout(timeout)?;
+            self.applied_read_timeout = timeout;
+        }
+
+        match self.stream.read(buf) {
+            Ok(len) => {
+                if len > 0 {
+                    self.read_timeout.data_received();
+                }
+                Ok(len)
+            }
+            // depending on the platform, an expired timeout is reported as `WouldBlock`
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock && timeout.is_some() => {
+                Err(IoError::new(ErrorKind::TimedOut, "Read timed out"))
+            }
+            Err(e) => Err(e),
+        }
     }
 }
 
@@ -0,0 +1,75 @@
+//! Calls `sendfile`, which the standard library doesn't use for sending files to sockets.
+
+use nix::errno::Errno;
+use std::fs::File;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::os::unix::io::AsRawFd;
+
+/// Largest number of bytes that Linux transfers with a single call.
+const MAX_CHUNK: u64 = 0x7fff_f000;
+
+/// Sends `length` bytes of a file, starting at its current position, to a socket. The data
+/// is copied by the kernel without going through userspace.
+///
+/// Returns the number of bytes that remain to be sent, which is more than zero only if the
+/// file doesn't support `sendfile`, such as the files of `/proc`. The caller must then copy
+/// them instead.
+///
+/// As with any zero-copy mechanism, modifying the file while it is being sent can change the
+/// data received by the client, including the data of calls that have already returned.
+pub fn sendfile<S: AsRawFd>(file: &File, socket: &S, length: u64) -> IoResult<u64> {
+    let mut remaining = length;
+    while remaining > 0 {
+        let count = remaining.min(MAX_CHUNK) as usize;
+
+        // without an offset, the kernel reads from the current position of the file and
+        // updates it
+        match nix::sys::sendfile::sendfile(socket.as_raw_fd(), file.as_raw_fd(), None, count) {
+            Ok(0) => {
+                return Err(IoError::new(
+                    ErrorKind::UnexpectedEof,
+                    "The file is shorter than expected",
+                ))
+            }
+            Ok(sent) => remaining -= sent as u64,
+            Err(Errno::EINTR) => (),
+            Err(Errno::EINVAL) | Err(Errno::ENOSYS) if remaining == length => return Ok(length),
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sendfile;
+    use std::fs::{self, File};
+    use std::io::{Read, Seek, SeekFrom};
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn test_sendfile() {
+        let path = std::env::temp_dir().join(format!("tiny-http-sendfile-{}", std::process::id()));
+        fs::write(&path, b"0123456789").unwrap();
+        let mut file = File::open(&path).unwrap();
+        file.seek(SeekFrom::Start(2)).unwrap();
+
+        let (sender, mut receiver) = UnixStream::pair().unwrap();
+        assert_eq!(sendfile(&file, &sender, 5).unwrap(), 0);
+        drop(sender);
+
+        let mut received = String::new();
+        receiver.read_to_string(&mut received).unwrap();
+        assert_eq!(received, "23456");
+
+        // the position of the file is updated
+        let mut rest = String::new();
+        file.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "789");
+
+        let (sender, _receiver) = UnixStream::pair().unwrap();
+        assert!(sendfile(&file, &sender, 1).is_err());
+        fs::remove_file(&path).ok();
+    }
+}
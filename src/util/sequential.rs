@@ -64,6 +64,27 @@ impl<R: Read + Send> SequentialReaderBuilder<R> {
     }
 }
 
+impl<R: Read + Send> SequentialReader<R> {
+    /// Blocks until all the readers built before this one have been destroyed.
+    pub fn wait_turn(&mut self) {
+        let reader = match self.inner {
+            SequentialReaderInner::Waiting(ref mut recv) => recv.recv().unwrap(),
+            _ => return,
+        };
+
+        self.inner = SequentialReaderInner::MyTurn(reader);
+    }
+
+    /// Waits for the turn of this reader, then gives access to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.wait_turn();
+        match self.inner {
+            SequentialReaderInner::MyTurn(ref mut reader) => reader,
+            _ => unreachable!(),
+        }
+    }
+}
+
 impl<W: Write + Send> SequentialWriterBuilder<W> {
     pub fn new(writer: W) -> SequentialWriterBuilder<W> {
         SequentialWriterBuilder {
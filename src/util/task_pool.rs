@@ -4,60 +4,71 @@ use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
 use std::time::Duration;
 
+use crate::Workers;
+
+type Task = Box<dyn FnMut() + Send>;
+
 /// Manages a collection of threads.
 ///
-/// A new thread is created every time all the existing threads are full.
-/// Any idle thread will automatically die after a few seconds.
+/// A new thread is created every time all the existing threads are full, up to a maximum.
+/// Once the maximum is reached, tasks wait in a queue of limited length.
+/// Any idle thread above the minimum will automatically die after a few seconds.
 pub struct TaskPool {
     sharing: Arc<Sharing>,
 }
 
 struct Sharing {
     // list of the tasks to be done by worker threads
-    todo: Mutex<VecDeque<Box<dyn FnMut() + Send>>>,
+    todo: Mutex<VecDeque<Task>>,
 
     // condvar that will be notified whenever a task is added to `todo`
     condvar: Condvar,
 
+    // condvar that will be notified whenever a task is removed from `todo`, or a thread
+    // finishes a task or exits
+    room: Condvar,
+
     // number of total worker threads running
     active_tasks: AtomicUsize,
 
-    // number of idle worker threads
-    waiting_tasks: AtomicUsize,
-}
+    // number of worker threads running a task
+    busy_tasks: AtomicUsize,
 
-/// Minimum number of active threads.
-static MIN_THREADS: usize = 4;
+    // number of threads that are kept alive even when idle
+    min_threads: usize,
 
-struct Registration<'a> {
-    nb: &'a AtomicUsize,
-}
+    // maximum number of threads, `None` if unlimited
+    max_threads: Option<usize>,
 
-impl<'a> Registration<'a> {
-    fn new(nb: &'a AtomicUsize) -> Registration<'a> {
-        nb.fetch_add(1, Ordering::Release);
-        Registration { nb }
-    }
-}
+    // maximum number of tasks waiting for a thread once `max_threads` is reached
+    max_queued: usize,
 
-impl<'a> Drop for Registration<'a> {
-    fn drop(&mut self) {
-        self.nb.fetch_sub(1, Ordering::Release);
-    }
+    // time after which an idle thread above `min_threads` dies
+    idle_timeout: Duration,
 }
 
 impl TaskPool {
-    pub fn new() -> TaskPool {
+    pub fn new(config: &Workers) -> TaskPool {
+        let min_threads = match config.max {
+            Some(max) => config.min.min(max),
+            None => config.min,
+        };
+
         let pool = TaskPool {
             sharing: Arc::new(Sharing {
                 todo: Mutex::new(VecDeque::new()),
                 condvar: Condvar::new(),
+                room: Condvar::new(),
                 active_tasks: AtomicUsize::new(0),
-                waiting_tasks: AtomicUsize::new(0),
+                busy_tasks: AtomicUsize::new(0),
+                min_threads,
+                max_threads: config.max,
+                max_queued: config.accept_queue,
+                idle_timeout: config.idle_timeout,
             }),
         };
 
-        for _ in 0..MIN_THREADS {
+        for _ in 0..min_threads {
             pool.add_thread(None)
         }
 
@@ -65,53 +76,90 @@ impl TaskPool {
     }
 
     /// Executes a function in a thread.
-    /// If no thread is available, spawns a new one.
-    pub fn spawn(&self, code: Box<dyn FnMut() + Send>) {
+    /// If no thread is available, spawns a new one, or blocks until there is room in the queue
+    /// if the maximum number of threads has been reached.
+    pub fn spawn(&self, code: Task) {
         let mut queue = self.sharing.todo.lock().unwrap();
 
-        if self.sharing.waiting_tasks.load(Ordering::Acquire) == 0 {
+        while !self.has_room(&queue) {
+            queue = self.sharing.room.wait(queue).unwrap();
+        }
+
+        if queue.len() >= self.idle_threads() && !self.max_threads_reached() {
             self.add_thread(Some(code));
         } else {
+            // picked up by an idle thread, or by the next thread that becomes idle
             queue.push_back(code);
             self.sharing.condvar.notify_one();
         }
     }
 
-    fn add_thread(&self, initial_fn: Option<Box<dyn FnMut() + Send>>) {
+    /// Returns true if a call to `spawn()` would block.
+    pub fn is_saturated(&self) -> bool {
+        let queue = self.sharing.todo.lock().unwrap();
+        !self.has_room(&queue)
+    }
+
+    // must be called with the `todo` lock held
+    fn has_room(&self, queue: &VecDeque<Task>) -> bool {
+        let idle = self.idle_threads();
+
+        queue.len() < idle
+            || !self.max_threads_reached()
+            || queue.len() - idle < self.sharing.max_queued
+    }
+
+    fn idle_threads(&self) -> usize {
+        let active = self.sharing.active_tasks.load(Ordering::Acquire);
+        let busy = self.sharing.busy_tasks.load(Ordering::Acquire);
+        active.saturating_sub(busy)
+    }
+
+    fn max_threads_reached(&self) -> bool {
+        let active = self.sharing.active_tasks.load(Ordering::Acquire);
+        self.sharing.max_threads.map_or(false, |max| active >= max)
+    }
+
+    fn add_thread(&self, initial_fn: Option<Task>) {
         let sharing = self.sharing.clone();
 
+        // registering here rather than in the new thread, so that `spawn` sees it right away
+        sharing.active_tasks.fetch_add(1, Ordering::Release);
+        let mut initial_fn = initial_fn.map(|f| (Busy::new(&sharing), f));
+
         thread::spawn(move || {
             let sharing = sharing;
-            let _active_guard = Registration::new(&sharing.active_tasks);
+            let _active_guard = Active(&sharing);
 
-            if let Some(mut f) = initial_fn {
+            if let Some((_busy_guard, mut f)) = initial_fn.take() {
                 f();
             }
 
             loop {
-                let mut task: Box<dyn FnMut() + Send> = {
+                let (_busy_guard, mut task) = {
                     let mut todo = sharing.todo.lock().unwrap();
 
                     let task;
                     loop {
                         if let Some(poped_task) = todo.pop_front() {
-                            task = poped_task;
+                            sharing.room.notify_one();
+                            task = (Busy::new(&sharing), poped_task);
                             break;
                         }
-                        let _waiting_guard = Registration::new(&sharing.waiting_tasks);
-
-                        let received =
-                            if sharing.active_tasks.load(Ordering::Acquire) <= MIN_THREADS {
-                                todo = sharing.condvar.wait(todo).unwrap();
-                                true
-                            } else {
-                                let (new_lock, waitres) = sharing
-                                    .condvar
-                                    .wait_timeout(todo, Duration::from_millis(5000))
-                                    .unwrap();
-                                todo = new_lock;
-                                !waitres.timed_out()
-                            };
+
+                        let received = if sharing.active_tasks.load(Ordering::Acquire)
+                            <= sharing.min_threads
+                        {
+                            todo = sharing.condvar.wait(todo).unwrap();
+                            true
+                        } else {
+                            let (new_lock, waitres) = sharing
+                                .condvar
+                                .wait_timeout(todo, sharing.idle_timeout)
+                                .unwrap();
+                            todo = new_lock;
+                            !waitres.timed_out()
+                        };
 
                         if !received && todo.is_empty() {
                             return;
@@ -127,6 +175,37 @@ impl TaskPool {
     }
 }
 
+/// Counts a thread as running a task until it is destroyed.
+///
+/// Must be created while the `todo` lock is held.
+struct Busy(Arc<Sharing>);
+
+impl Busy {
+    fn new(sharing: &Arc<Sharing>) -> Busy {
+        sharing.busy_tasks.fetch_add(1, Ordering::Release);
+        Busy(sharing.clone())
+    }
+}
+
+impl Drop for Busy {
+    fn drop(&mut self) {
+        let _todo = self.0.todo.lock().unwrap();
+        self.0.busy_tasks.fetch_sub(1, Ordering::Release);
+        self.0.room.notify_one();
+    }
+}
+
+/// Unregisters a worker thread when it exits.
+struct Active<'a>(&'a Sharing);
+
+impl<'a> Drop for Active<'a> {
+    fn drop(&mut self) {
+        let _todo = self.0.todo.lock().unwrap();
+        self.0.active_tasks.fetch_sub(1, Ordering::Release);
+        self.0.room.notify_one();
+    }
+}
+
 impl Drop for TaskPool {
     fn drop(&mut self) {
         self.sharing
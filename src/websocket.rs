@@ -0,0 +1,467 @@
+//! WebSocket connections, as described by [RFC 6455](https://www.rfc-editor.org/rfc/rfc6455).
+//!
+//! This module requires the `websocket` feature. [`WebSocket::accept`] checks that a request
+//! asks for a WebSocket, answers it with `Request::upgrade` and then sends and receives
+//! whole messages over the connection.
+//!
+//! ```no_run
+//! use tiny_http::websocket::{self, Message, WebSocket};
+//!
+//! # let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+//! for request in server.incoming_requests() {
+//!     if !websocket::is_upgrade_request(&request) {
+//!         let _ = request.respond(tiny_http::Response::from_string("hello world"));
+//!         continue;
+//!     }
+//!
+//!     std::thread::spawn(move || {
+//!         let mut socket = match WebSocket::accept(request) {
+//!             Ok(socket) => socket,
+//!             Err(_) => return,
+//!         };
+//!
+//!         // echoing the text messages
+//!         while let Ok(message) = socket.recv() {
+//!             match message {
+//!                 Message::Text(text) => socket.send(Message::Text(text)).unwrap(),
+//!                 Message::Close(_) => break,
+//!                 _ => (),
+//!             }
+//!         }
+//!     });
+//! }
+//! ```
+
+use crate::{Header, Method, ReadWrite, Request, Response};
+use std::io::{self, Error as IoError, ErrorKind, Read, Result as IoResult, Write};
+
+/// Appended to the key sent by the client to compute the `Sec-WebSocket-Accept` header.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_INVALID_DATA: u16 = 1007;
+const CLOSE_TOO_BIG: u16 = 1009;
+
+/// A message sent or received over a WebSocket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// A text message.
+    Text(String),
+    /// A binary message.
+    Binary(Vec<u8>),
+    /// A ping. The pings received are answered automatically with a pong carrying the same
+    /// data.
+    Ping(Vec<u8>),
+    /// A pong, answering a ping or sent as a heartbeat.
+    Pong(Vec<u8>),
+    /// Starts or answers the closing handshake, with an optional status code and reason.
+    Close(Option<CloseFrame>),
+}
+
+/// Status code and reason of a close message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseFrame {
+    /// Status code, for instance `1000` for a normal closure or `1001` when the server is going
+    /// away.
+    pub code: u16,
+    /// Human-readable reason, at most 123 bytes long.
+    pub reason: String,
+}
+
+/// Returns true if the request asks to switch to the WebSocket protocol.
+///
+/// This only looks at the `Upgrade` header. [`WebSocket::accept`] checks the rest of the
+/// handshake.
+pub fn is_upgrade_request(request: &Request) -> bool {
+    has_token(request, "Upgrade", "websocket")
+}
+
+/// A WebSocket connection on the server side.
+pub struct WebSocket {
+    stream: Box<dyn ReadWrite + Send>,
+
+    // subprotocol chosen during the handshake
+    protocol: Option<String>,
+
+    max_message_size: usize,
+
+    // opcode and data of the fragments of a message received so far
+    fragments: Option<(u8, Vec<u8>)>,
+
+    // true once a close message has been sent, after which only control messages may be received
+    close_sent: bool,
+
+    // true once a close message has been received, or the connection failed
+    closed: bool,
+}
+
+impl WebSocket {
+    /// Checks the handshake of a WebSocket client and answers it with a
+    /// `101 Switching Protocols` response.
+    ///
+    /// If the request isn't a valid handshake, it is answered with a `400 Bad Request` response,
+    /// or `426 Upgrade Required` if the client uses an unsupported version of the protocol, and
+    /// an error is returned.
+    pub fn accept(request: Request) -> IoResult<WebSocket> {
+        WebSocket::accept_with_protocols(request, &[])
+    }
+
+    /// Same as `accept`, also choosing a subprotocol.
+    ///
+    /// The subprotocol is the first one listed by the client in its `Sec-WebSocket-Protocol`
+    /// headers which is part of `protocols`. If there is none, the handshake succeeds without a
+    /// subprotocol and the client may decide to close the connection.
+    pub fn accept_with_protocols(request: Request, protocols: &[&str]) -> IoResult<WebSocket> {
+        let accept_key = match check_handshake(&request) {
+            Ok(accept_key) => accept_key,
+            Err((response, message)) => {
+                request.respond(response)?;
+                return Err(IoError::new(ErrorKind::InvalidData, message));
+            }
+        };
+
+        let protocol = tokens(&request, "Sec-WebSocket-Protocol")
+            .find(|requested| protocols.contains(requested))
+            .map(str::to_owned);
+
+        let mut response =
+            Response::empty(101).with_header(header("Sec-WebSocket-Accept", &accept_key));
+        if let Some(ref protocol) = protocol {
+            response.add_header(header("Sec-WebSocket-Protocol", protocol));
+        }
+
+        Ok(WebSocket {
+            stream: request.upgrade("websocket", response),
+            protocol,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            fragments: None,
+            close_sent: false,
+            closed: false,
+        })
+    }
+
+    /// Returns the subprotocol chosen during the handshake, if any.
+    #[inline]
+    pub fn protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+
+    /// Sets the maximum size in bytes of the messages received. Larger messages close the
+    /// connection with a `1009` status code and make `recv()` fail.
+    ///
+    /// Defaults to 16 MiB.
+    #[inline]
+    pub fn set_max_message_size(&mut self, size: usize) {
+        self.max_message_size = size;
+    }
+
+    /// Blocks until the next message is received.
+    ///
+    /// Fragmented messages are put back together, and the pings are answered before being
+    /// returned. Once a close message is received, it is answered if it doesn't answer one sent
+    /// with `send()`, and the next calls fail.
+    ///
+    /// If the client breaks the protocol, for instance by sending unmasked frames or invalid
+    /// UTF-8 in a text message, the connection is closed with the matching status code and an
+    /// `InvalidData` error is returned.
+    pub fn recv(&mut self) -> IoResult<Message> {
+        if self.closed {
+            return Err(IoError::new(
+                ErrorKind::NotConnected,
+                "The WebSocket is closed",
+            ));
+        }
+
+        loop {
+            let (fin, opcode, payload) = self.read_frame()?;
+            match opcode {
+                OPCODE_PING => {
+                    if !self.close_sent {
+                        self.write_frame(OPCODE_PONG, &payload)?;
+                    }
+                    return Ok(Message::Ping(payload));
+                }
+                OPCODE_PONG => return Ok(Message::Pong(payload)),
+                OPCODE_CLOSE => {
+                    let frame = self.parse_close(&payload)?;
+                    self.closed = true;
+                    if !self.close_sent {
+                        let answer = frame.as_ref().map(|frame| CloseFrame {
+                            code: frame.code,
+                            reason: String::new(),
+                        });
+                        // the client may not wait for the answer before disconnecting
+                        let _ = self.close(answer);
+                    }
+                    return Ok(Message::Close(frame));
+                }
+                OPCODE_TEXT | OPCODE_BINARY if self.fragments.is_none() => {
+                    if fin {
+                        return self.message(opcode, payload);
+                    }
+                    self.fragments = Some((opcode, payload));
+                }
+                OPCODE_CONTINUATION if self.fragments.is_some() => {
+                    let (opcode, mut data) = self.fragments.take().unwrap();
+                    data.extend_from_slice(&payload);
+                    if fin {
+                        return self.message(opcode, data);
+                    }
+                    self.fragments = Some((opcode, data));
+                }
+                OPCODE_TEXT | OPCODE_BINARY | OPCODE_CONTINUATION => {
+                    return Err(self.fail(CLOSE_PROTOCOL_ERROR, "Unexpected message fragment"))
+                }
+                _ => return Err(self.fail(CLOSE_PROTOCOL_ERROR, "Unknown WebSocket opcode")),
+            }
+        }
+    }
+
+    /// Sends a message.
+    ///
+    /// Sending a `Message::Close` starts the closing handshake: `recv()` then returns the
+    /// close message of the client once it arrives, and no other message can be sent. The
+    /// control messages, pings, pongs and closes, may carry at most 125 bytes.
+    pub fn send(&mut self, message: Message) -> IoResult<()> {
+        if self.close_sent {
+            return Err(IoError::new(
+                ErrorKind::NotConnected,
+                "The WebSocket is closing",
+            ));
+        }
+
+        match message {
+            Message::Text(text) => self.write_frame(OPCODE_TEXT, text.as_bytes()),
+            Message::Binary(data) => self.write_frame(OPCODE_BINARY, &data),
+            Message::Ping(data) => self.write_control(OPCODE_PING, &data),
+            Message::Pong(data) => self.write_control(OPCODE_PONG, &data),
+            Message::Close(frame) => self.close(frame),
+        }
+    }
+
+    fn close(&mut self, frame: Option<CloseFrame>) -> IoResult<()> {
+        let mut payload = Vec::new();
+        if let Some(frame) = frame {
+            payload.extend_from_slice(&frame.code.to_be_bytes());
+            payload.extend_from_slice(frame.reason.as_bytes());
+        }
+        self.write_control(OPCODE_CLOSE, &payload)?;
+        self.close_sent = true;
+        Ok(())
+    }
+
+    /// Reads the next frame, returning whether it is the final fragment, its opcode and its
+    /// unmasked payload.
+    fn read_frame(&mut self) -> IoResult<(bool, u8, Vec<u8>)> {
+        let mut header = [0; 2];
+        self.read_exact(&mut header)?;
+
+        let fin = header[0] & 0x80 != 0;
+        let opcode = header[0] & 0x0F;
+        if header[0] & 0x70 != 0 {
+            return Err(self.fail(CLOSE_PROTOCOL_ERROR, "Reserved bits set in WebSocket frame"));
+        }
+        // the frames sent by clients must always be masked
+        if header[1] & 0x80 == 0 {
+            return Err(self.fail(CLOSE_PROTOCOL_ERROR, "Unmasked WebSocket frame"));
+        }
+
+        let len = match header[1] & 0x7F {
+            126 => {
+                let mut len = [0; 2];
+                self.read_exact(&mut len)?;
+                u64::from(u16::from_be_bytes(len))
+            }
+            127 => {
+                let mut len = [0; 8];
+                self.read_exact(&mut len)?;
+                u64::from_be_bytes(len)
+            }
+            len => u64::from(len),
+        };
+
+        if opcode & 0x8 != 0 {
+            if !fin || len > 125 {
+                return Err(self.fail(CLOSE_PROTOCOL_ERROR, "Invalid WebSocket control frame"));
+            }
+        } else {
+            let received = self.fragments.as_ref().map_or(0, |(_, data)| data.len());
+            if len > self.max_message_size.saturating_sub(received) as u64 {
+                return Err(self.fail(CLOSE_TOO_BIG, "WebSocket message too large"));
+            }
+        }
+
+        let mut mask = [0; 4];
+        self.read_exact(&mut mask)?;
+        let mut payload = vec![0; len as usize];
+        self.read_exact(&mut payload)?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+
+        Ok((fin, opcode, payload))
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> IoResult<()> {
+        let result = self.stream.read_exact(buf);
+        if result.is_err() {
+            self.closed = true;
+        }
+        result
+    }
+
+    fn message(&mut self, opcode: u8, data: Vec<u8>) -> IoResult<Message> {
+        if opcode == OPCODE_BINARY {
+            return Ok(Message::Binary(data));
+        }
+
+        match String::from_utf8(data) {
+            Ok(text) => Ok(Message::Text(text)),
+            Err(_) => Err(self.fail(CLOSE_INVALID_DATA, "Invalid UTF-8 in text message")),
+        }
+    }
+
+    fn parse_close(&mut self, payload: &[u8]) -> IoResult<Option<CloseFrame>> {
+        if payload.is_empty() {
+            return Ok(None);
+        }
+        if payload.len() < 2 {
+            return Err(self.fail(CLOSE_PROTOCOL_ERROR, "Invalid close message"));
+        }
+
+        let code = u16::from_be_bytes([payload[0], payload[1]]);
+        // 1004 is reserved, and 1005, 1006 and 1015 must never be sent
+        let valid_code = match code {
+            1000..=1003 | 1007..=1014 | 3000..=4999 => true,
+            _ => false,
+        };
+        if !valid_code {
+            return Err(self.fail(CLOSE_PROTOCOL_ERROR, "Invalid close status code"));
+        }
+
+        match String::from_utf8(payload[2..].to_vec()) {
+            Ok(reason) => Ok(Some(CloseFrame { code, reason })),
+            Err(_) => Err(self.fail(CLOSE_INVALID_DATA, "Invalid UTF-8 in close reason")),
+        }
+    }
+
+    /// Closes the connection after a protocol error and returns the error to report.
+    fn fail(&mut self, code: u16, message: &'static str) -> IoError {
+        if !self.close_sent {
+            let _ = self.close(Some(CloseFrame {
+                code,
+                reason: String::new(),
+            }));
+        }
+        self.closed = true;
+        IoError::new(ErrorKind::InvalidData, message)
+    }
+
+    fn write_control(&mut self, opcode: u8, payload: &[u8]) -> IoResult<()> {
+        if payload.len() > 125 {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                "WebSocket control messages can't be longer than 125 bytes",
+            ));
+        }
+        self.write_frame(opcode, payload)
+    }
+
+    fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> IoResult<()> {
+        // a single final fragment, never masked on the server side
+        let mut header = Vec::with_capacity(10);
+        header.push(0x80 | opcode);
+        match payload.len() {
+            len if len < 126 => header.push(len as u8),
+            len if len <= usize::from(u16::MAX) => {
+                header.push(126);
+                header.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                header.push(127);
+                header.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+
+        self.stream.write_all(&header)?;
+        self.stream.write_all(payload)?;
+        self.stream.flush()
+    }
+}
+
+/// Checks the handshake and returns the value of the `Sec-WebSocket-Accept` header, or the
+/// response to send and the error to return.
+fn check_handshake(request: &Request) -> Result<String, (Response<io::Empty>, &'static str)> {
+    if *request.method() != Method::Get
+        || *request.http_version() < (1, 1)
+        || !is_upgrade_request(request)
+        || !has_token(request, "Connection", "upgrade")
+    {
+        return Err((Response::empty(400), "Not a WebSocket handshake"));
+    }
+
+    if !tokens(request, "Sec-WebSocket-Version").any(|version| version == "13") {
+        let response = Response::empty(426).with_header(header("Sec-WebSocket-Version", "13"));
+        return Err((response, "Unsupported WebSocket version"));
+    }
+
+    let key = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Sec-WebSocket-Key"))
+        .map(|h| h.value.as_str().trim())
+        .filter(|key| base64::decode(key).map_or(false, |key| key.len() == 16));
+    match key {
+        Some(key) => Ok(accept_key(key)),
+        None => Err((Response::empty(400), "Invalid Sec-WebSocket-Key header")),
+    }
+}
+
+/// Computes the value of the `Sec-WebSocket-Accept` header from the `Sec-WebSocket-Key` one.
+fn accept_key(key: &str) -> String {
+    let mut sha1 = sha1::Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(ACCEPT_GUID.as_bytes());
+    base64::encode(sha1.digest().bytes())
+}
+
+/// Returns the comma-separated values of all the headers named `field`.
+fn tokens<'a>(request: &'a Request, field: &'static str) -> impl Iterator<Item = &'a str> {
+    request
+        .headers()
+        .iter()
+        .filter(move |h| h.field.equiv(field))
+        .flat_map(|h| h.value.as_str().split(','))
+        .map(str::trim)
+}
+
+fn has_token(request: &Request, field: &'static str, token: &str) -> bool {
+    tokens(request, field).any(|value| value.eq_ignore_ascii_case(token))
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field.as_bytes(), value.as_bytes()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::accept_key;
+
+    #[test]
+    fn test_accept_key() {
+        // from RFC 6455
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+}
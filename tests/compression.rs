@@ -0,0 +1,314 @@
+#![cfg(feature = "compression")]
+
+extern crate tiny_http;
+
+use flate2::read::{GzDecoder, GzEncoder, ZlibDecoder, ZlibEncoder};
+use flate2::Compression;
+use std::io::{Read, Write};
+
+#[allow(dead_code)]
+mod support;
+
+/// Sends `response` with compression enabled to a request with the given extra headers, and
+/// returns what the client received.
+fn exchange<R: Read>(request_headers: &str, response: tiny_http::Response<R>) -> Vec<u8> {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n",
+        request_headers
+    )
+    .unwrap();
+
+    server
+        .recv()
+        .unwrap()
+        .respond(response.with_compression())
+        .unwrap();
+
+    let mut content = Vec::new();
+    client.read_to_end(&mut content).unwrap();
+    content
+}
+
+fn head(content: &[u8]) -> String {
+    let end = content.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+    String::from_utf8(content[..end + 4].to_vec()).unwrap()
+}
+
+/// Removes the chunked transfer encoding from a body.
+fn dechunk(mut body: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    loop {
+        let end = body.windows(2).position(|w| w == b"\r\n").unwrap();
+        let size = std::str::from_utf8(&body[..end]).unwrap();
+        let size = usize::from_str_radix(size, 16).unwrap();
+        if size == 0 {
+            return data;
+        }
+        data.extend_from_slice(&body[end + 2..end + 2 + size]);
+        body = &body[end + 2 + size + 2..];
+    }
+}
+
+fn decode<R: Read>(mut decoder: R) -> String {
+    let mut output = String::new();
+    decoder.read_to_string(&mut output).unwrap();
+    output
+}
+
+fn text() -> String {
+    "Lorem ipsum dolor sit amet. ".repeat(200)
+}
+
+#[test]
+fn gzip_compression() {
+    let content = exchange(
+        "Accept-Encoding: gzip, deflate\r\n",
+        tiny_http::Response::from_string(text()),
+    );
+
+    let head = head(&content);
+    assert!(head.contains("Content-Encoding: gzip\r\n"));
+    assert!(head.contains("Vary: Accept-Encoding\r\n"));
+    assert!(head.contains("Transfer-Encoding: chunked\r\n"));
+    assert!(!head.contains("Content-Length"));
+
+    let body = dechunk(&content[head.len()..]);
+    assert!(body.len() < text().len() / 4);
+    assert_eq!(decode(GzDecoder::new(&body[..])), text());
+}
+
+#[test]
+fn brotli_preferred_by_default() {
+    let content = exchange(
+        "Accept-Encoding: gzip, deflate, br\r\n",
+        tiny_http::Response::from_string(text()),
+    );
+
+    let head = head(&content);
+    assert!(head.contains("Content-Encoding: br\r\n"));
+    let body = dechunk(&content[head.len()..]);
+    assert_eq!(decode(brotli::Decompressor::new(&body[..], 4096)), text());
+}
+
+#[test]
+fn deflate_preferred_by_quality() {
+    let content = exchange(
+        "Accept-Encoding: gzip;q=0.5, deflate\r\n",
+        tiny_http::Response::from_string(text()),
+    );
+    let head = head(&content);
+    assert!(head.contains("Content-Encoding: deflate\r\n"));
+    let body = dechunk(&content[head.len()..]);
+    assert_eq!(decode(ZlibDecoder::new(&body[..])), text());
+}
+
+#[test]
+fn refused_codings() {
+    let content = exchange(
+        "Accept-Encoding: zstd, gzip;q=0, *;q=0\r\n",
+        tiny_http::Response::from_string(text()),
+    );
+
+    let head = head(&content);
+    assert!(!head.contains("Content-Encoding"));
+    // the response could have been different with another `Accept-Encoding`
+    assert!(head.contains("Vary: Accept-Encoding\r\n"));
+    assert!(head.contains(&format!("Content-Length: {}\r\n", text().len())));
+}
+
+#[test]
+fn no_compression_without_accept_encoding() {
+    let content = exchange("", tiny_http::Response::from_string(text()));
+
+    let head = head(&content);
+    assert!(!head.contains("Content-Encoding"));
+    assert!(content.ends_with(text().as_bytes()));
+}
+
+#[test]
+fn small_bodies_are_not_compressed() {
+    let content = exchange(
+        "Accept-Encoding: gzip\r\n",
+        tiny_http::Response::from_string("hello world"),
+    );
+
+    let head = head(&content);
+    assert!(!head.contains("Content-Encoding"));
+    assert!(!head.contains("Vary"));
+    assert!(content.ends_with(b"\r\n\r\nhello world"));
+}
+
+#[test]
+fn compressed_types_are_not_compressed() {
+    let response = tiny_http::Response::from_data(vec![0; 4096]).with_header(
+        "Content-Type: image/png"
+            .parse::<tiny_http::Header>()
+            .unwrap(),
+    );
+    let content = exchange("Accept-Encoding: gzip\r\n", response);
+
+    let head = head(&content);
+    assert!(!head.contains("Content-Encoding"));
+    assert!(head.contains("Content-Length: 4096\r\n"));
+}
+
+#[test]
+fn existing_content_encoding_is_kept() {
+    let response = tiny_http::Response::from_data(vec![0; 4096])
+        .with_header("Content-Encoding: br".parse::<tiny_http::Header>().unwrap());
+    let content = exchange("Accept-Encoding: gzip\r\n", response);
+
+    let head = head(&content);
+    assert!(head.contains("Content-Encoding: br\r\n"));
+    assert!(!head.contains("Content-Encoding: gzip"));
+    assert!(head.contains("Content-Length: 4096\r\n"));
+}
+
+#[test]
+fn http_1_0_compression() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(client, "GET / HTTP/1.0\r\nAccept-Encoding: gzip\r\n\r\n").unwrap();
+
+    let response = tiny_http::Response::from_string(text()).with_compression();
+    server.recv().unwrap().respond(response).unwrap();
+
+    let mut content = Vec::new();
+    client.read_to_end(&mut content).unwrap();
+
+    // the compressed body is buffered to know its length
+    let head = head(&content);
+    assert!(head.contains("Content-Encoding: gzip\r\n"));
+    let length = content.len() - head.len();
+    assert!(head.contains(&format!("Content-Length: {}\r\n", length)));
+    assert_eq!(decode(GzDecoder::new(&content[head.len()..])), text());
+}
+
+fn compressed_request(
+    server: &tiny_http::Server,
+    client: &mut std::net::TcpStream,
+    content_encoding: &str,
+    body: &[u8],
+) -> tiny_http::Request {
+    (write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Encoding: {}\r\nContent-Length: {}\r\n\r\n",
+        content_encoding,
+        body.len()
+    ))
+    .unwrap();
+    client.write_all(body).unwrap();
+
+    server.recv().unwrap()
+}
+
+fn encode<R: Read>(mut encoder: R) -> Vec<u8> {
+    let mut output = Vec::new();
+    encoder.read_to_end(&mut output).unwrap();
+    output
+}
+
+const JSON: &str = r#"{"hello":"world"}"#;
+
+#[test]
+fn gzip_body_decompression() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let body = encode(GzEncoder::new(JSON.as_bytes(), Compression::default()));
+    let mut request = compressed_request(&server, &mut client, "gzip", &body);
+
+    request.decompress_body(1024);
+    assert_eq!(request.body_length(), None);
+    assert_eq!(decode(request.as_reader()), JSON);
+}
+
+#[test]
+fn deflate_body_decompression() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let body = encode(ZlibEncoder::new(JSON.as_bytes(), Compression::default()));
+    let mut request = compressed_request(&server, &mut client, "deflate", &body);
+
+    request.decompress_body(1024);
+    assert_eq!(decode(request.as_reader()), JSON);
+}
+
+#[test]
+fn brotli_body_decompression() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let body = encode(brotli::CompressorReader::new(JSON.as_bytes(), 4096, 5, 22));
+    let mut request = compressed_request(&server, &mut client, "br", &body);
+
+    request.decompress_body(1024);
+    assert_eq!(decode(request.as_reader()), JSON);
+}
+
+#[test]
+fn decompressed_body_too_large() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let body = encode(GzEncoder::new(JSON.as_bytes(), Compression::default()));
+    let mut request = compressed_request(&server, &mut client, "gzip", &body);
+
+    request.decompress_body(10);
+    let mut output = Vec::new();
+    let err = request.as_reader().read_to_end(&mut output).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(output, br#"{"hello":""#);
+    drop(request);
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 413"));
+    assert!(content.contains("Connection: close\r\n"));
+}
+
+#[test]
+fn stacked_codings() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let body = encode(GzEncoder::new(JSON.as_bytes(), Compression::default()));
+    let body = encode(ZlibEncoder::new(&body[..], Compression::default()));
+    let mut request = compressed_request(&server, &mut client, "gzip, deflate", &body);
+
+    request.decompress_body(1024);
+    assert_eq!(decode(request.as_reader()), JSON);
+}
+
+#[test]
+fn too_many_stacked_codings() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let body = encode(GzEncoder::new(JSON.as_bytes(), Compression::default()));
+    let body = encode(GzEncoder::new(&body[..], Compression::default()));
+    let body = encode(GzEncoder::new(&body[..], Compression::default()));
+    let mut request = compressed_request(&server, &mut client, "gzip, gzip, gzip", &body);
+
+    request.decompress_body(1024);
+    assert!(request.as_reader().read(&mut [0; 16]).is_err());
+    drop(request);
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 415"));
+}
+
+#[test]
+fn identity_body_is_unchanged() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let mut request = compressed_request(&server, &mut client, "identity", b"hello");
+
+    request.decompress_body(1024);
+    assert_eq!(request.body_length(), Some(5));
+    assert_eq!(decode(request.as_reader()), "hello");
+}
+
+#[test]
+fn unsupported_body_encoding() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let mut request = compressed_request(&server, &mut client, "compress", b"hello");
+
+    request.decompress_body(1024);
+    assert!(request.as_reader().read(&mut [0; 16]).is_err());
+    drop(request);
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 415"));
+}
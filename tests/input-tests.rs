@@ -120,3 +120,154 @@ fn custom_content_type_response_header() {
     assert!(content.ends_with("{\"custom\": \"Content-Type\"}"));
     assert_ne!(content.find("Content-Type: application/json"), None);
 }
+
+#[test]
+fn request_line_too_long() {
+    let (_server, mut client) = support::new_one_server_one_client();
+
+    let path = "a".repeat(10 * 1024);
+    (write!(client, "GET /{} HTTP/1.1\r\nHost: localhost\r\n\r\n", path)).unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 414"));
+}
+
+#[test]
+fn header_line_too_long() {
+    let (_server, mut client) = support::new_one_server_one_client();
+
+    let value = "a".repeat(10 * 1024);
+    (write!(client, "GET / HTTP/1.1\r\nX-Big: {}\r\n\r\n", value)).unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 431"));
+}
+
+#[test]
+fn too_many_headers() {
+    let (_server, mut client) = support::new_one_server_one_client();
+
+    (write!(client, "GET / HTTP/1.1\r\n")).unwrap();
+    for i in 0..200 {
+        (write!(client, "X-Header-{}: value\r\n", i)).unwrap();
+    }
+    (write!(client, "\r\n")).unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 431"));
+}
+
+#[test]
+fn headers_within_limits() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    let value = "a".repeat(4 * 1024);
+    (write!(
+        client,
+        "GET / HTTP/1.1\r\nX-Big: {}\r\nConnection: close\r\n\r\n",
+        value
+    ))
+    .unwrap();
+
+    let request = server.recv().unwrap();
+    assert_eq!(request.headers()[0].value.as_str(), value);
+}
+
+fn body_limits(max_body_size: usize) -> tiny_http::Limits {
+    let mut limits = tiny_http::Limits::default();
+    limits.max_body_size = Some(max_body_size);
+    limits
+}
+
+#[test]
+fn body_too_large() {
+    let (_server, mut client) = support::new_one_server_one_client_with_limits(body_limits(4));
+
+    (write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello"
+    ))
+    .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 413"));
+}
+
+#[test]
+fn body_within_limit() {
+    let (server, mut client) = support::new_one_server_one_client_with_limits(body_limits(5));
+
+    (write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello"
+    ))
+    .unwrap();
+
+    let mut request = server.recv().unwrap();
+    let mut output = String::new();
+    request.as_reader().read_to_string(&mut output).unwrap();
+    assert_eq!(output, "hello");
+}
+
+#[test]
+fn chunked_body_too_large() {
+    let (server, mut client) = support::new_one_server_one_client_with_limits(body_limits(8));
+
+    (write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n5\r\nworld\r\n0\r\n\r\n"
+    ))
+    .unwrap();
+
+    let mut request = server.recv().unwrap();
+    let mut output = Vec::new();
+    let err = request.as_reader().read_to_end(&mut output).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(output, b"hellowor");
+}
+
+#[test]
+fn chunked_body_too_large_closes_connection() {
+    let (server, mut client) = support::new_one_server_one_client_with_limits(body_limits(8));
+
+    (write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\na\r\nhellohello\r\n0\r\n\r\n"
+    ))
+    .unwrap();
+
+    let mut request = server.recv().unwrap();
+    assert!(request.as_reader().read_to_end(&mut Vec::new()).is_err());
+    drop(request);
+
+    // the end of the body must not be parsed as another request
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 413"));
+    assert!(content.contains("Connection: close"));
+    assert_eq!(content.matches("HTTP/1.1").count(), 1);
+}
+
+#[test]
+fn request_body_limit() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    (write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: 5\r\n\r\nhello"
+    ))
+    .unwrap();
+
+    let mut request = server.recv().unwrap();
+    request.set_body_limit(4);
+    assert!(request.as_reader().read(&mut [0; 16]).is_err());
+    drop(request);
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 413"));
+}
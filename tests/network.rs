@@ -3,7 +3,7 @@ extern crate tiny_http;
 use std::io::{Read, Write};
 use std::net::{Shutdown, TcpStream};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 #[allow(dead_code)]
 mod support;
@@ -181,38 +181,85 @@ fn no_transfer_encoding_on_204() {
     assert!(!content.contains("Transfer-Encoding: chunked"));
 }
 
-/* FIXME: uncomment and fix
+fn new_server_with_timeouts(timeouts: tiny_http::Timeouts) -> (tiny_http::Server, TcpStream) {
+    let mut config = tiny_http::ServerConfig::new(
+        tiny_http::ConfigListenAddr::from_socket_addrs("0.0.0.0:0").unwrap(),
+    );
+    config.timeouts = timeouts;
+    let server = tiny_http::Server::new(config).unwrap();
+    let port = server.server_addr().to_ip().unwrap().port();
+    let client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    (server, client)
+}
+
 #[test]
 fn connection_timeout() {
-    let (server, mut client) = {
-        let server = tiny_http::ServerBuilder::new()
-            .with_client_connections_timeout(3000)
-            .with_random_port().build().unwrap();
-        let port = server.server_addr().port();
-        let client = TcpStream::connect(("127.0.0.1", port)).unwrap();
-        (server, client)
-    };
+    let mut timeouts = tiny_http::Timeouts::default();
+    timeouts.header = Some(Duration::from_millis(300));
+    let (_server, mut client) = new_server_with_timeouts(timeouts);
 
-    let (tx_stop, rx_stop) = mpsc::channel();
+    // waiting for the 408 response
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(&content[9..].starts_with("408"));
+}
 
-    // executing server in parallel
-    thread::spawn(move || {
-        loop {
-            server.try_recv();
-            thread::sleep(Duration::from_millis(100));
-            if rx_stop.try_recv().is_ok() { break }
+#[test]
+fn slow_header_timeout() {
+    let mut timeouts = tiny_http::Timeouts::default();
+    timeouts.header = Some(Duration::from_millis(500));
+    let (_server, mut client) = new_server_with_timeouts(timeouts);
+
+    // sending a byte every 100ms, which never triggers a per-read timeout
+    for &byte in b"GET / HTTP/1.1\r\nHost: localhost\r\n" {
+        if client.write_all(&[byte]).is_err() {
+            break;
         }
-    });
+        thread::sleep(Duration::from_millis(100));
+    }
 
-    // waiting for the 408 response
     let mut content = String::new();
     client.read_to_string(&mut content).unwrap();
     assert!(&content[9..].starts_with("408"));
+}
 
-    // stopping server
-    tx_stop.send(());
+#[test]
+fn keep_alive_timeout() {
+    let mut timeouts = tiny_http::Timeouts::default();
+    timeouts.keep_alive = Some(Duration::from_millis(300));
+    let (server, mut client) = new_server_with_timeouts(timeouts);
+
+    (write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+    let request = server.recv().unwrap();
+    request
+        .respond(tiny_http::Response::from_string("hello world"))
+        .unwrap();
+
+    // the idle connection is closed without any other response
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 200"));
+    assert!(content.ends_with("hello world"));
+}
+
+#[test]
+fn body_timeout() {
+    let mut timeouts = tiny_http::Timeouts::default();
+    timeouts.body = Some(Duration::from_millis(300));
+    let (server, mut client) = new_server_with_timeouts(timeouts);
+
+    (write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2000\r\n\r\nhello"
+    ))
+    .unwrap();
+
+    let mut request = server.recv().unwrap();
+    let mut body = Vec::new();
+    let err = request.as_reader().read_to_end(&mut body).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    assert_eq!(body, b"hello");
 }
-*/
 
 #[test]
 fn chunked_threshold() {
@@ -220,3 +267,247 @@ fn chunked_threshold() {
     assert_eq!(resp.chunked_threshold(), 32768);
     assert_eq!(resp.with_chunked_threshold(42).chunked_threshold(), 42);
 }
+
+fn new_server_with_workers(workers: tiny_http::Workers) -> (tiny_http::Server, u16) {
+    let mut config = tiny_http::ServerConfig::new(
+        tiny_http::ConfigListenAddr::from_socket_addrs("0.0.0.0:0").unwrap(),
+    );
+    config.workers = workers;
+    let server = tiny_http::Server::new(config).unwrap();
+    let port = server.server_addr().to_ip().unwrap().port();
+    (server, port)
+}
+
+#[test]
+fn saturated_workers_reject() {
+    let mut workers = tiny_http::Workers::default();
+    workers.min = 1;
+    workers.max = Some(1);
+    workers.accept_queue = 0;
+    workers.overload = tiny_http::Overload::Reject {
+        retry_after: Duration::from_millis(1500),
+    };
+    let (_server, port) = new_server_with_workers(workers);
+
+    // this connection keeps the only worker busy
+    let _busy = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    thread::sleep(Duration::from_millis(100));
+
+    let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    (write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 503"));
+    assert!(content.contains("Retry-After: 2\r\n"));
+    assert!(content.contains("Connection: close\r\n"));
+}
+
+#[test]
+fn saturated_workers_wait() {
+    let mut workers = tiny_http::Workers::default();
+    workers.min = 1;
+    workers.max = Some(1);
+    workers.accept_queue = 0;
+    let (server, port) = new_server_with_workers(workers);
+
+    let busy = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    thread::sleep(Duration::from_millis(100));
+
+    let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    (write!(client, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n")).unwrap();
+    assert!(server
+        .recv_timeout(Duration::from_millis(300))
+        .unwrap()
+        .is_none());
+
+    // the worker becomes available once the first connection is closed
+    drop(busy);
+    let request = server.recv().unwrap();
+    request
+        .respond(tiny_http::Response::from_string("hello world"))
+        .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("hello world"));
+}
+
+#[test]
+fn idle_connections_dont_starve_the_server() {
+    let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    let port = server.server_addr().to_ip().unwrap().port();
+
+    // enough connections to fill the workers and the accept queue of a server with a limit
+    let workers = tiny_http::Workers::default();
+    let count = workers.max.unwrap_or(256) + workers.accept_queue;
+    let _idle: Vec<_> = (0..count)
+        .map(|_| TcpStream::connect(("127.0.0.1", port)).unwrap())
+        .collect();
+
+    let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    (write!(client, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n")).unwrap();
+    let request = server
+        .recv_timeout(Duration::from_secs(10))
+        .unwrap()
+        .expect("the request wasn't received in time");
+    request
+        .respond(tiny_http::Response::from_string("hello world"))
+        .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("hello world"));
+}
+
+#[test]
+fn shutdown_finishes_in_flight_requests() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let server = std::sync::Arc::new(server);
+    let port = server.server_addr().to_ip().unwrap().port();
+
+    // an idle connection, which is closed right away
+    let mut idle_client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+
+    (write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+    let request = server.recv().unwrap();
+
+    let shutdown = {
+        let server = server.clone();
+        thread::spawn(move || server.shutdown(Instant::now() + Duration::from_secs(5)))
+    };
+
+    let mut content = String::new();
+    idle_client.read_to_string(&mut content).unwrap();
+    assert!(content.is_empty());
+
+    request
+        .respond(tiny_http::Response::from_string("hello world"))
+        .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 200"));
+    assert!(content.contains("Connection: close\r\n"));
+    assert!(content.ends_with("hello world"));
+
+    assert!(shutdown.join().unwrap());
+}
+
+#[test]
+fn shutdown_rejects_queued_requests() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    (write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+    while server.stats().requests_queued == 0 {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    assert!(server.shutdown(Instant::now() + Duration::from_secs(5)));
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 503"));
+    assert!(content.contains("Connection: close\r\n"));
+}
+
+#[test]
+fn shutdown_deadline() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    (write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+    let _request = server.recv().unwrap();
+
+    let start = Instant::now();
+    assert!(!server.shutdown(start + Duration::from_millis(200)));
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
+
+fn new_server_with_request_queue(
+    request_queue: tiny_http::RequestQueue,
+) -> (tiny_http::Server, u16) {
+    let mut config = tiny_http::ServerConfig::new(
+        tiny_http::ConfigListenAddr::from_socket_addrs("0.0.0.0:0").unwrap(),
+    );
+    config.request_queue = request_queue;
+    let server = tiny_http::Server::new(config).unwrap();
+    let port = server.server_addr().to_ip().unwrap().port();
+    (server, port)
+}
+
+#[test]
+fn full_request_queue_reject() {
+    let mut request_queue = tiny_http::RequestQueue::default();
+    request_queue.max_len = Some(1);
+    request_queue.overload = tiny_http::Overload::Reject {
+        retry_after: Duration::from_secs(1),
+    };
+    let (server, port) = new_server_with_request_queue(request_queue);
+
+    let mut first = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    (write!(first, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n")).unwrap();
+    while server.queue_len() == 0 {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    // the rejection closes the connection, even if the client wants to keep it alive
+    let mut second = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    (write!(second, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+
+    let mut content = String::new();
+    second.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 503"));
+    assert!(content.contains("Retry-After: 1\r\n"));
+    assert!(content.contains("Connection: close\r\n"));
+    assert_eq!(server.queue_len(), 1);
+}
+
+#[test]
+fn full_request_queue_wait() {
+    let mut request_queue = tiny_http::RequestQueue::default();
+    request_queue.max_len = Some(1);
+    let (server, port) = new_server_with_request_queue(request_queue);
+
+    let mut first = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    (write!(first, "GET /first HTTP/1.1\r\nConnection: close\r\n\r\n")).unwrap();
+    while server.queue_len() == 0 {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let mut second = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    (write!(second, "GET /second HTTP/1.1\r\nConnection: close\r\n\r\n")).unwrap();
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(server.queue_len(), 1);
+
+    // the second request enters the queue once the first one has left it
+    assert_eq!(server.recv().unwrap().url(), "/first");
+    assert_eq!(server.recv().unwrap().url(), "/second");
+}
+
+fn new_server_on(
+    addrs: Vec<std::net::SocketAddr>,
+) -> Result<tiny_http::Server, Box<dyn std::error::Error + Send + Sync>> {
+    tiny_http::Server::new(tiny_http::ServerConfig::new(
+        tiny_http::ConfigListenAddr::IP(addrs),
+    ))
+}
+
+#[test]
+fn listener_for_each_address() {
+    let addr = "127.0.0.1:0".parse().unwrap();
+    let server = new_server_on(vec![addr, addr]).unwrap();
+
+    let addrs = server.server_addrs();
+    assert_eq!(addrs.len(), 2);
+    let second = addrs[1].clone().to_ip().unwrap();
+    assert_ne!(addrs[0].clone().to_ip(), Some(second));
+
+    let mut client = TcpStream::connect(second).unwrap();
+    (write!(client, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n")).unwrap();
+    let request = server.recv().unwrap();
+    assert_eq!(request.listen_addr().unwrap().clone().to_ip(), Some(second));
+
+    // every address must be bound, and there must be at least one
+    let unavailable = "192.0.2.1:0".parse().unwrap();
+    assert!(new_server_on(vec![addr, unavailable]).is_err());
+    assert!(new_server_on(Vec::new()).is_err());
+}
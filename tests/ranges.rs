@@ -0,0 +1,153 @@
+extern crate tiny_http;
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+
+#[allow(dead_code)]
+mod support;
+
+/// The content of the file served by the tests.
+fn content() -> String {
+    "0123456789".repeat(10)
+}
+
+fn file_response(name: &str) -> tiny_http::Response<File> {
+    let path =
+        std::env::temp_dir().join(format!("tiny-http-ranges-{}-{}", std::process::id(), name));
+    fs::write(&path, content()).unwrap();
+    tiny_http::Response::from_file(File::open(&path).unwrap())
+}
+
+/// Sends a response for a file to a request with the given extra headers, and returns what the
+/// client received.
+fn exchange(request_headers: &str, response: tiny_http::Response<File>) -> String {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n",
+        request_headers
+    )
+    .unwrap();
+
+    server.recv().unwrap().respond(response).unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    content
+}
+
+#[test]
+fn whole_file_without_range() {
+    let content = exchange("", file_response("whole"));
+    assert!(content.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(content.contains("Accept-Ranges: bytes\r\n"));
+    assert!(content.ends_with(&format!("\r\n\r\n{}", self::content())));
+}
+
+#[test]
+fn single_range() {
+    let content = exchange("Range: bytes=12-15\r\n", file_response("single"));
+    assert!(content.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+    assert!(content.contains("Content-Range: bytes 12-15/100\r\n"));
+    assert!(content.contains("Content-Length: 4\r\n"));
+    assert!(content.ends_with("\r\n\r\n2345"));
+}
+
+#[test]
+fn suffix_range() {
+    let content = exchange("Range: bytes=-3\r\n", file_response("suffix"));
+    assert!(content.contains("Content-Range: bytes 97-99/100\r\n"));
+    assert!(content.ends_with("\r\n\r\n789"));
+}
+
+#[test]
+fn multiple_ranges() {
+    let response = file_response("multiple").with_header(
+        "Content-Type: text/plain"
+            .parse::<tiny_http::Header>()
+            .unwrap(),
+    );
+    let content = exchange("Range: bytes=90-92, 0-1\r\n", response);
+    assert!(content.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+
+    let boundary = content
+        .split("Content-Type: multipart/byteranges; boundary=")
+        .nth(1)
+        .unwrap()
+        .split("\r\n")
+        .next()
+        .unwrap();
+
+    let body = content.split_once("\r\n\r\n").unwrap().1;
+    let expected = format!(
+        "\r\n--{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/100\r\n\r\n01\
+         \r\n--{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 90-92/100\r\n\r\n012\
+         \r\n--{b}--\r\n",
+        b = boundary
+    );
+    assert_eq!(body, expected);
+    assert!(content.contains(&format!("Content-Length: {}\r\n", expected.len())));
+}
+
+#[test]
+fn unsatisfiable_range() {
+    let content = exchange("Range: bytes=100-\r\n", file_response("unsatisfiable"));
+    assert!(content.starts_with("HTTP/1.1 416 Range Not Satisfiable\r\n"));
+    assert!(content.contains("Content-Range: bytes */100\r\n"));
+    assert!(content.ends_with("Content-Length: 0\r\n\r\n"));
+}
+
+#[test]
+fn invalid_range_is_ignored() {
+    let content = exchange("Range: bytes=5-1\r\n", file_response("invalid"));
+    assert!(content.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(content.ends_with(&self::content()));
+}
+
+#[test]
+fn if_range() {
+    let etag = || "ETag: \"v2\"".parse::<tiny_http::Header>().unwrap();
+
+    let content = exchange(
+        "Range: bytes=0-1\r\nIf-Range: \"v2\"\r\n",
+        file_response("if-range-match").with_header(etag()),
+    );
+    assert!(content.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+    assert!(content.ends_with("\r\n\r\n01"));
+
+    // the file changed since the client got its first part
+    let content = exchange(
+        "Range: bytes=0-1\r\nIf-Range: \"v1\"\r\n",
+        file_response("if-range-changed").with_header(etag()),
+    );
+    assert!(content.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(content.ends_with(&self::content()));
+}
+
+#[test]
+fn large_file_with_content_length() {
+    // large files are sent directly to the socket rather than in chunks
+    let data: Vec<u8> = (0..3_000_000u32).map(|i| (i % 251) as u8).collect();
+    let path = std::env::temp_dir().join(format!("tiny-http-ranges-{}-large", std::process::id()));
+    fs::write(&path, &data).unwrap();
+
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let response = tiny_http::Response::from_file(File::open(&path).unwrap());
+    let thread = std::thread::spawn(move || server.recv().unwrap().respond(response).unwrap());
+
+    let mut content = Vec::new();
+    client.read_to_end(&mut content).unwrap();
+    thread.join().unwrap();
+
+    let head_end = content.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    let head = String::from_utf8_lossy(&content[..head_end]);
+    assert!(head.contains("Content-Length: 3000000\r\n"));
+    assert!(!head.contains("Transfer-Encoding"));
+    assert!(content[head_end..] == data[..]);
+}
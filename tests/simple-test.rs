@@ -27,3 +27,32 @@ fn basic_handling() {
     stream.read_to_string(&mut content).unwrap();
     assert!(content.ends_with("hello world"));
 }
+
+#[test]
+fn server_stats() {
+    use std::thread;
+    use std::time::Duration;
+
+    let (server, mut stream) = support::new_one_server_one_client();
+    write!(stream, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+    // waiting for the request to be parsed by the connection thread
+    while server.stats().requests_queued == 0 {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(server.num_connections(), 1);
+
+    let request = server.recv().unwrap();
+    let stats = server.stats();
+    assert_eq!(stats.connections, 1);
+    assert_eq!(stats.requests_in_flight, 1);
+    assert_eq!(stats.requests_queued, 0);
+
+    request.respond(tiny_http::Response::empty(204)).unwrap();
+    assert_eq!(server.stats().requests_in_flight, 0);
+
+    drop(stream);
+    while server.num_connections() != 0 {
+        thread::sleep(Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,91 @@
+extern crate tiny_http;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+#[allow(dead_code)]
+mod support;
+
+/// Reads from the client until `expected` has been received, failing after a few seconds.
+fn read_until(client: &mut TcpStream, expected: &str) -> String {
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let mut received = Vec::new();
+    let mut buf = [0; 256];
+    while !String::from_utf8_lossy(&received).contains(expected) {
+        let len = client.read(&mut buf).unwrap();
+        assert!(len > 0, "connection closed before {:?}", expected);
+        received.extend_from_slice(&buf[..len]);
+    }
+    String::from_utf8(received).unwrap()
+}
+
+#[test]
+fn events_are_sent_right_away() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "GET /events HTTP/1.1\r\nHost: localhost\r\nLast-Event-ID: 41\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut sender = server.recv().unwrap().into_sse().unwrap();
+    assert_eq!(sender.last_event_id(), Some("41"));
+
+    let head = read_until(&mut client, "\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains("Content-Type: text/event-stream\r\n"));
+    assert!(head.contains("Transfer-Encoding: chunked\r\n"));
+
+    sender
+        .send(&tiny_http::SseEvent::new("hello\nworld").with_id("42"))
+        .unwrap();
+    read_until(&mut client, "id: 42\ndata: hello\ndata: world\n\n");
+
+    sender.keep_alive().unwrap();
+    read_until(&mut client, ":\n\n");
+
+    // the end of the stream
+    drop(sender);
+    read_until(&mut client, "0\r\n\r\n");
+}
+
+#[test]
+fn events_over_http_1_0() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(client, "GET /events HTTP/1.0\r\n\r\n").unwrap();
+
+    let mut sender = server.recv().unwrap().into_sse().unwrap();
+    assert_eq!(sender.last_event_id(), None);
+    sender.send_data("hello").unwrap();
+    drop(sender);
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.contains("Connection: close\r\n"));
+    assert!(!content.contains("Transfer-Encoding"));
+    assert!(content.ends_with("\r\n\r\ndata: hello\n\n"));
+}
+
+#[test]
+fn events_over_http_1_0_keep_alive() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "GET /events HTTP/1.0\r\nConnection: keep-alive\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut sender = server.recv().unwrap().into_sse().unwrap();
+    sender.send_data("hello").unwrap();
+    drop(sender);
+
+    // the end of the events is marked by closing the connection
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.contains("Connection: close\r\n"));
+    assert!(content.ends_with("\r\n\r\ndata: hello\n\n"));
+}
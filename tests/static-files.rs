@@ -0,0 +1,152 @@
+extern crate tiny_http;
+
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+
+use tiny_http::static_files::StaticFiles;
+use tiny_http::{Header, Method, Request, ResponseBox, TestRequest};
+
+/// Creates a directory with a few files, unique to the test.
+fn site(name: &str) -> PathBuf {
+    let root =
+        std::env::temp_dir().join(format!("tiny-http-static-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("docs")).unwrap();
+    fs::write(root.join("style.css"), "body {}").unwrap();
+    fs::write(root.join("docs/index.html"), "<h1>docs</h1>").unwrap();
+    root
+}
+
+fn get(path: &str) -> TestRequest {
+    TestRequest::new().with_path(path)
+}
+
+fn respond(files: &StaticFiles, request: TestRequest) -> Option<ResponseBox> {
+    let request: Request = request.into();
+    files.response(&request)
+}
+
+fn header<'a>(response: &'a ResponseBox, name: &'static str) -> Option<&'a str> {
+    response
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str())
+}
+
+fn body(response: ResponseBox) -> String {
+    let mut body = String::new();
+    response.into_reader().read_to_string(&mut body).unwrap();
+    body
+}
+
+#[test]
+fn serves_files() {
+    let files = StaticFiles::new("/static", site("serves"));
+
+    let response = respond(&files, get("/static/style.css?v=2")).unwrap();
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        header(&response, "Content-Type"),
+        Some("text/css; charset=utf-8")
+    );
+    assert!(header(&response, "ETag").is_some());
+    assert!(header(&response, "Last-Modified").is_some());
+    assert_eq!(body(response), "body {}");
+
+    let response = respond(&files, get("/static/missing.txt")).unwrap();
+    assert_eq!(response.status_code(), 404);
+
+    assert!(respond(&files, get("/other/style.css")).is_none());
+}
+
+#[test]
+fn directory_index() {
+    let files = StaticFiles::new("/", site("index"));
+
+    let response = respond(&files, get("/docs/")).unwrap();
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        header(&response, "Content-Type"),
+        Some("text/html; charset=utf-8")
+    );
+    assert_eq!(body(response), "<h1>docs</h1>");
+
+    let response = respond(&files, get("/docs?page=1")).unwrap();
+    assert_eq!(response.status_code(), 301);
+    assert_eq!(header(&response, "Location"), Some("/docs/?page=1"));
+
+    // no index file in the root
+    let response = respond(&files, get("/")).unwrap();
+    assert_eq!(response.status_code(), 404);
+
+    let files = files.with_index_files(&["style.css"]);
+    let response = respond(&files, get("/")).unwrap();
+    assert_eq!(body(response), "body {}");
+}
+
+#[test]
+fn path_traversal() {
+    let root = site("traversal");
+    fs::write(root.parent().unwrap().join("secret.txt"), "secret").unwrap();
+    let files = StaticFiles::new("/static", root.join("docs"));
+
+    for path in &[
+        "/static/../style.css",
+        "/static/%2e%2e/style.css",
+        "/static/..%2fstyle.css",
+        "/static/./../../secret.txt",
+    ] {
+        let response = respond(&files, get(path)).unwrap();
+        assert_eq!(response.status_code(), 404, "{}", path);
+    }
+
+    let response = respond(&files, get("/static/%2e%2e/index.html")).unwrap();
+    assert_eq!(body(response), "<h1>docs</h1>");
+
+    let response = respond(&files, get("/static/..%5cstyle.css")).unwrap();
+    assert_eq!(response.status_code(), 400);
+}
+
+#[test]
+fn conditional_requests() {
+    let files = StaticFiles::new("/", site("conditional"));
+
+    let response = respond(&files, get("/style.css")).unwrap();
+    let etag = header(&response, "ETag").unwrap().to_owned();
+    let last_modified = header(&response, "Last-Modified").unwrap().to_owned();
+
+    let conditional = |name: &str, value: &str| {
+        let header = Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap();
+        respond(&files, get("/style.css").with_header(header)).unwrap()
+    };
+
+    let response = conditional("If-None-Match", &format!("\"other\", {}", etag));
+    assert_eq!(response.status_code(), 304);
+    assert_eq!(header(&response, "ETag"), Some(etag.as_str()));
+
+    let response = conditional("If-None-Match", &format!("W/{}", etag));
+    assert_eq!(response.status_code(), 304);
+
+    let response = conditional("If-None-Match", "\"other\"");
+    assert_eq!(response.status_code(), 200);
+
+    let response = conditional("If-Modified-Since", &last_modified);
+    assert_eq!(response.status_code(), 304);
+
+    let response = conditional("If-Modified-Since", "Thu, 01 Jan 1998 00:00:00 GMT");
+    assert_eq!(response.status_code(), 200);
+}
+
+#[test]
+fn only_get_and_head() {
+    let files = StaticFiles::new("/", site("methods"));
+
+    let response = respond(&files, get("/style.css").with_method(Method::Head)).unwrap();
+    assert_eq!(response.status_code(), 200);
+
+    let response = respond(&files, get("/style.css").with_method(Method::Post)).unwrap();
+    assert_eq!(response.status_code(), 405);
+    assert_eq!(header(&response, "Allow"), Some("GET, HEAD"));
+}
@@ -0,0 +1,159 @@
+extern crate tiny_http;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+#[allow(dead_code)]
+mod support;
+
+/// Reads from the client until `expected` has been received, failing after a few seconds.
+fn read_until(client: &mut TcpStream, expected: &str) -> String {
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let mut received = Vec::new();
+    let mut buf = [0; 256];
+    while !String::from_utf8_lossy(&received).contains(expected) {
+        let len = client.read(&mut buf).unwrap();
+        assert!(len > 0, "connection closed before {:?}", expected);
+        received.extend_from_slice(&buf[..len]);
+    }
+    String::from_utf8(received).unwrap()
+}
+
+#[test]
+fn one_chunk_per_flush() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+    let request = server.recv().unwrap();
+    let header = "Content-Type: text/plain".parse().unwrap();
+    let mut response = request.into_streaming_response(202, vec![header]).unwrap();
+
+    let head = read_until(&mut client, "\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 202 Accepted\r\n"));
+    assert!(head.contains("Content-Type: text/plain\r\n"));
+    assert!(head.contains("Transfer-Encoding: chunked\r\n"));
+
+    response.write_all(b"hello ").unwrap();
+    response.write_all(b"world").unwrap();
+    response.flush().unwrap();
+    read_until(&mut client, "b\r\nhello world\r\n");
+
+    response.write_all(b"!").unwrap();
+    response.add_trailer("X-Checksum: 1234".parse().unwrap());
+    response.finish().unwrap();
+    read_until(&mut client, "1\r\n!\r\n0\r\nX-Checksum: 1234\r\n\r\n");
+}
+
+#[test]
+fn body_ends_when_dropped() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "GET /first HTTP/1.1\r\nHost: localhost\r\n\r\n\
+         GET /second HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut response = server
+        .recv()
+        .unwrap()
+        .into_streaming_response(200, vec![])
+        .unwrap();
+    write!(response, "first").unwrap();
+    drop(response);
+
+    // the connection is still usable
+    server
+        .recv()
+        .unwrap()
+        .respond(tiny_http::Response::from_string("second"))
+        .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.contains("\r\n\r\n5\r\nfirst\r\n0\r\n\r\nHTTP/1.1 200 OK\r\n"));
+    assert!(content.ends_with("\r\n\r\nsecond"));
+}
+
+#[test]
+fn head_request_has_no_body() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "HEAD / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut response = server
+        .recv()
+        .unwrap()
+        .into_streaming_response(200, vec![])
+        .unwrap();
+    write!(response, "ignored").unwrap();
+    response.add_trailer("X-Checksum: 1234".parse().unwrap());
+    drop(response);
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.contains("Transfer-Encoding: chunked\r\n"));
+    assert!(content.ends_with("\r\n\r\n"));
+    assert!(!content.contains("ignored"));
+}
+
+#[test]
+fn no_body_with_204() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "GET /first HTTP/1.1\r\nHost: localhost\r\n\r\n\
+         GET /second HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut response = server
+        .recv()
+        .unwrap()
+        .into_streaming_response(204, vec![])
+        .unwrap();
+    write!(response, "ignored").unwrap();
+    drop(response);
+
+    // the next response isn't mixed up with the data written
+    server
+        .recv()
+        .unwrap()
+        .respond(tiny_http::Response::from_string("second"))
+        .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 204"));
+    assert!(!content.contains("ignored"));
+    assert!(content.contains("\r\n\r\nHTTP/1.1 200 OK\r\n"));
+    assert!(content.ends_with("\r\n\r\nsecond"));
+}
+
+#[test]
+fn no_trailers_over_http_1_0() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(client, "GET / HTTP/1.0\r\nTE: trailers\r\n\r\n").unwrap();
+
+    let mut response = server
+        .recv()
+        .unwrap()
+        .into_streaming_response(200, vec!["Trailer: X-Checksum".parse().unwrap()])
+        .unwrap();
+    write!(response, "hello").unwrap();
+    response.add_trailer("X-Checksum: 1234".parse().unwrap());
+    drop(response);
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(!content.contains("Trailer"));
+    assert!(!content.contains("X-Checksum"));
+    assert!(content.ends_with("\r\n\r\nhello"));
+}
@@ -10,6 +10,20 @@ pub fn new_one_server_one_client() -> (tiny_http::Server, TcpStream) {
     (server, client)
 }
 
+/// Creates a server with the given limits and a client connected to the server.
+pub fn new_one_server_one_client_with_limits(
+    limits: tiny_http::Limits,
+) -> (tiny_http::Server, TcpStream) {
+    let mut config = tiny_http::ServerConfig::new(
+        tiny_http::ConfigListenAddr::from_socket_addrs("0.0.0.0:0").unwrap(),
+    );
+    config.limits = limits;
+    let server = tiny_http::Server::new(config).unwrap();
+    let port = server.server_addr().to_ip().unwrap().port();
+    let client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    (server, client)
+}
+
 /// Creates a "hello world" server with a client connected to the server.
 ///
 /// The server will automatically close after 3 seconds.
@@ -0,0 +1,178 @@
+extern crate tiny_http;
+
+use std::io::{Cursor, Read, Write};
+use std::sync::mpsc::{self, Sender};
+
+#[allow(dead_code)]
+mod support;
+
+/// Sends the length of the data read as a header once the end of the data is reached.
+struct LengthReader {
+    inner: Cursor<Vec<u8>>,
+    read: usize,
+    sender: Sender<tiny_http::Header>,
+}
+
+impl Read for LengthReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.read += len;
+        if len == 0 {
+            let header = format!("X-Length: {}", self.read).parse().unwrap();
+            let _ = self.sender.send(header);
+        }
+        Ok(len)
+    }
+}
+
+fn response_with_trailer() -> tiny_http::Response<LengthReader> {
+    let (sender, receiver) = mpsc::channel();
+    let reader = LengthReader {
+        inner: Cursor::new(b"hello world".to_vec()),
+        read: 0,
+        sender,
+    };
+    tiny_http::Response::new(
+        200.into(),
+        vec!["Trailer: X-Length".parse().unwrap()],
+        reader,
+        Some(11),
+        Some(receiver),
+    )
+}
+
+#[test]
+fn trailers_sent_after_the_body() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nTE: trailers\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    server
+        .recv()
+        .unwrap()
+        .respond(response_with_trailer())
+        .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.contains("Trailer: X-Length\r\n"));
+    assert!(content.contains("Transfer-Encoding: chunked\r\n"));
+    assert!(!content.contains("Content-Length"));
+    assert!(content.ends_with("\r\n\r\nb\r\nhello world\r\n0\r\nX-Length: 11\r\n\r\n"));
+}
+
+#[test]
+fn no_trailers_unless_requested() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    sender.send("X-Early: 1".parse().unwrap()).unwrap();
+    let response = tiny_http::Response::new(
+        200.into(),
+        vec!["Trailer: X-Late".parse().unwrap()],
+        Cursor::new(b"hello world".to_vec()),
+        Some(11),
+        Some(receiver),
+    );
+    server.recv().unwrap().respond(response).unwrap();
+    // the response doesn't wait for the later ones
+    let _ = sender.send("X-Late: 2".parse().unwrap());
+
+    // the headers available before the response is sent are part of the usual ones
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.contains("X-Early: 1\r\n"));
+    assert!(!content.contains("Trailer"));
+    assert!(!content.contains("X-Late"));
+    assert!(content.ends_with("Content-Length: 11\r\n\r\nhello world"));
+}
+
+#[test]
+fn no_trailers_over_http_1_0() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(client, "GET / HTTP/1.0\r\nTE: trailers\r\n\r\n").unwrap();
+
+    server
+        .recv()
+        .unwrap()
+        .respond(response_with_trailer())
+        .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(!content.contains("Trailer"));
+    assert!(!content.contains("X-Length"));
+    assert!(content.ends_with("\r\n\r\nhello world"));
+}
+
+#[test]
+fn forbidden_trailers_dropped() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nTE: trailers\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    let response = tiny_http::Response::new(
+        200.into(),
+        vec!["Trailer: X-Length".parse().unwrap()],
+        Cursor::new(b"hello world".to_vec()),
+        Some(11),
+        Some(receiver),
+    );
+    let request = server.recv().unwrap();
+    sender.send("Content-Length: 5".parse().unwrap()).unwrap();
+    sender.send("Set-Cookie: a=b".parse().unwrap()).unwrap();
+    sender.send("X-Length: 11".parse().unwrap()).unwrap();
+    request.respond(response).unwrap();
+
+    // the fields needed before the body can't be sent after it
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(!content.contains("Content-Length"));
+    assert!(!content.contains("Set-Cookie"));
+    assert!(content.ends_with("\r\n\r\nb\r\nhello world\r\n0\r\nX-Length: 11\r\n\r\n"));
+}
+
+#[test]
+fn trailers_announced_automatically() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nTE: trailers\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    let response = tiny_http::Response::new(
+        200.into(),
+        Vec::new(),
+        Cursor::new(b"hello world".to_vec()),
+        Some(11),
+        Some(receiver),
+    );
+    let request = server.recv().unwrap();
+    sender.send("X-Checksum: 1".parse().unwrap()).unwrap();
+    sender.send("Set-Cookie: a=b".parse().unwrap()).unwrap();
+    sender.send("X-Length: 11".parse().unwrap()).unwrap();
+    sender.send("x-length: 12".parse().unwrap()).unwrap();
+    request.respond(response).unwrap();
+
+    // the names of the trailers available when the response is sent are listed once
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.contains("Trailer: X-Checksum, X-Length\r\n"));
+    assert!(content.ends_with(
+        "\r\n\r\nb\r\nhello world\r\n0\r\nX-Checksum: 1\r\nX-Length: 11\r\nx-length: 12\r\n\r\n"
+    ));
+}
@@ -4,6 +4,7 @@ extern crate tiny_http;
 
 use std::{
     io::{Read, Write},
+    net::TcpStream,
     os::unix::net::UnixStream,
     path::{Path, PathBuf},
 };
@@ -42,3 +43,47 @@ fn unix_basic_handling() {
     client.read_to_string(&mut content).unwrap();
     assert!(content.ends_with("hello world"));
 }
+
+#[test]
+fn tcp_and_unix_listeners() {
+    let socket_path = Path::new("/tmp/tiny-http-test-listeners.sock");
+    let _ = std::fs::remove_file(socket_path);
+
+    let mut config = tiny_http::ServerConfig::new(
+        tiny_http::ConfigListenAddr::from_socket_addrs("127.0.0.1:0").unwrap(),
+    );
+    config.additional_listeners = vec![
+        tiny_http::ListenerConfig::new(
+            tiny_http::ConfigListenAddr::from_socket_addrs("127.0.0.1:0").unwrap(),
+            None,
+        ),
+        tiny_http::ListenerConfig::new(
+            tiny_http::ConfigListenAddr::unix_from_path(socket_path),
+            None,
+        ),
+    ];
+    let server = tiny_http::Server::new(config).unwrap();
+
+    let addrs = server.server_addrs();
+    assert_eq!(addrs.len(), 3);
+    let first = addrs[0].clone().to_ip().unwrap();
+    let second = addrs[1].clone().to_ip().unwrap();
+    assert_ne!(first, second);
+    assert_eq!(server.server_addr().to_ip(), Some(first));
+
+    let mut unix_client = UnixStream::connect(socket_path).unwrap();
+    write!(
+        unix_client,
+        "GET /unix HTTP/1.1\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    let request = server.recv().unwrap();
+    assert_eq!(request.url(), "/unix");
+    assert!(request.listen_addr().unwrap().clone().to_unix().is_some());
+
+    let mut tcp_client = TcpStream::connect(second).unwrap();
+    write!(tcp_client, "GET /tcp HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+    let request = server.recv().unwrap();
+    assert_eq!(request.url(), "/tcp");
+    assert_eq!(request.listen_addr().unwrap().clone().to_ip(), Some(second));
+}
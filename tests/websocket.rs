@@ -0,0 +1,194 @@
+#![cfg(feature = "websocket")]
+
+extern crate tiny_http;
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::thread;
+
+use tiny_http::websocket::{self, CloseFrame, Message, WebSocket};
+
+#[allow(dead_code)]
+mod support;
+
+/// Sends the handshake of RFC 6455 and returns the response of the server.
+fn handshake(client: &mut TcpStream, extra_headers: &str) -> String {
+    write!(
+        client,
+        "GET /chat HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n{}\r\n",
+        extra_headers
+    )
+    .unwrap();
+
+    let mut response = Vec::new();
+    let mut byte = [0];
+    while !response.ends_with(b"\r\n\r\n") {
+        client.read_exact(&mut byte).unwrap();
+        response.push(byte[0]);
+    }
+    String::from_utf8(response).unwrap()
+}
+
+/// Receives a request and accepts the WebSocket handshake in the background.
+fn accept(server: tiny_http::Server) -> thread::JoinHandle<WebSocket> {
+    thread::spawn(move || {
+        let request = server.recv().unwrap();
+        assert!(websocket::is_upgrade_request(&request));
+        WebSocket::accept_with_protocols(request, &["chat"]).unwrap()
+    })
+}
+
+/// Builds a frame masked like the clients must do.
+fn client_frame(first_byte: u8, payload: &[u8]) -> Vec<u8> {
+    assert!(payload.len() < 126);
+    let mask = [0x12, 0x34, 0x56, 0x78];
+    let mut frame = vec![first_byte, 0x80 | payload.len() as u8];
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    frame
+}
+
+/// Reads a short frame sent by the server, returning its first byte and its payload.
+fn read_server_frame(client: &mut TcpStream) -> (u8, Vec<u8>) {
+    let mut header = [0; 2];
+    client.read_exact(&mut header).unwrap();
+    assert_eq!(header[1] & 0x80, 0, "the server must not mask its frames");
+    let mut payload = vec![0; usize::from(header[1])];
+    client.read_exact(&mut payload).unwrap();
+    (header[0], payload)
+}
+
+#[test]
+fn handshake_and_echo() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let socket = accept(server);
+
+    let response = handshake(
+        &mut client,
+        "Sec-WebSocket-Version: 13\r\nSec-WebSocket-Protocol: superchat, chat\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 101"));
+    assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+    assert!(response.contains("Sec-WebSocket-Protocol: chat\r\n"));
+
+    let mut socket = socket.join().unwrap();
+    assert_eq!(socket.protocol(), Some("chat"));
+
+    client.write_all(&client_frame(0x81, b"hello")).unwrap();
+    assert_eq!(socket.recv().unwrap(), Message::Text("hello".to_owned()));
+
+    socket.send(Message::Binary(vec![1, 2, 3])).unwrap();
+    assert_eq!(read_server_frame(&mut client), (0x82, vec![1, 2, 3]));
+}
+
+#[test]
+fn fragmented_message_with_ping() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let socket = accept(server);
+    handshake(&mut client, "Sec-WebSocket-Version: 13\r\n");
+    let mut socket = socket.join().unwrap();
+
+    client.write_all(&client_frame(0x01, b"hel")).unwrap();
+    client.write_all(&client_frame(0x89, b"ping")).unwrap();
+    client.write_all(&client_frame(0x80, b"lo")).unwrap();
+
+    // the ping in the middle of the message is answered right away
+    assert_eq!(socket.recv().unwrap(), Message::Ping(b"ping".to_vec()));
+    assert_eq!(read_server_frame(&mut client), (0x8A, b"ping".to_vec()));
+    assert_eq!(socket.recv().unwrap(), Message::Text("hello".to_owned()));
+}
+
+#[test]
+fn close_handshake() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let socket = accept(server);
+    handshake(&mut client, "Sec-WebSocket-Version: 13\r\n");
+    let mut socket = socket.join().unwrap();
+
+    client
+        .write_all(&client_frame(0x88, b"\x03\xe8bye"))
+        .unwrap();
+    assert_eq!(
+        socket.recv().unwrap(),
+        Message::Close(Some(CloseFrame {
+            code: 1000,
+            reason: "bye".to_owned(),
+        }))
+    );
+    assert_eq!(read_server_frame(&mut client), (0x88, b"\x03\xe8".to_vec()));
+
+    assert_eq!(socket.recv().unwrap_err().kind(), ErrorKind::NotConnected);
+    assert_eq!(
+        socket
+            .send(Message::Text("late".to_owned()))
+            .unwrap_err()
+            .kind(),
+        ErrorKind::NotConnected
+    );
+}
+
+#[test]
+fn protocol_errors_close_the_connection() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let socket = accept(server);
+    handshake(&mut client, "Sec-WebSocket-Version: 13\r\n");
+    let mut socket = socket.join().unwrap();
+
+    // an unmasked frame
+    client.write_all(&[0x81, 0x02, b'h', b'i']).unwrap();
+    assert_eq!(socket.recv().unwrap_err().kind(), ErrorKind::InvalidData);
+    assert_eq!(read_server_frame(&mut client), (0x88, b"\x03\xea".to_vec()));
+}
+
+#[test]
+fn invalid_text_message() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let socket = accept(server);
+    handshake(&mut client, "Sec-WebSocket-Version: 13\r\n");
+    let mut socket = socket.join().unwrap();
+
+    client.write_all(&client_frame(0x81, b"\xff\xfe")).unwrap();
+    assert_eq!(socket.recv().unwrap_err().kind(), ErrorKind::InvalidData);
+    assert_eq!(read_server_frame(&mut client), (0x88, b"\x03\xef".to_vec()));
+}
+
+#[test]
+fn message_too_large() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let socket = accept(server);
+    handshake(&mut client, "Sec-WebSocket-Version: 13\r\n");
+    let mut socket = socket.join().unwrap();
+    socket.set_max_message_size(4);
+
+    client.write_all(&client_frame(0x02, b"abc")).unwrap();
+    client.write_all(&client_frame(0x80, b"de")).unwrap();
+    assert_eq!(socket.recv().unwrap_err().kind(), ErrorKind::InvalidData);
+    assert_eq!(read_server_frame(&mut client), (0x88, b"\x03\xf1".to_vec()));
+}
+
+#[test]
+fn unsupported_version() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let result = thread::spawn(move || WebSocket::accept(server.recv().unwrap()).map(|_| ()));
+
+    let response = handshake(&mut client, "Sec-WebSocket-Version: 8\r\n");
+    assert!(response.starts_with("HTTP/1.1 426"));
+    assert!(response.contains("Sec-WebSocket-Version: 13\r\n"));
+    assert_eq!(
+        result.join().unwrap().unwrap_err().kind(),
+        ErrorKind::InvalidData
+    );
+}
+
+#[test]
+fn not_a_websocket_handshake() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let result = thread::spawn(move || WebSocket::accept(server.recv().unwrap()).map(|_| ()));
+
+    write!(client, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+    let mut response = String::new();
+    client.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 400"));
+    assert!(result.join().unwrap().is_err());
+}